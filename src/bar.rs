@@ -1,74 +1,272 @@
-use item_state::ItemState;
-use bar_builder::UpdateStream;
+use bar_builder::{self, BarBuilder, Outputs};
+use config::Config;
+use panel::{self, Panel, PanelTemplate};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 use std::rc::Rc;
-use xcb::{Connection, Rectangle, Window};
-use futures::stream::{Merge, MergedItem};
+use xcb::{Connection, Rectangle, Window, randr};
 use futures::{Future, Stream};
-use xcb_event_stream::XcbEventStream;
+use futures::sync::oneshot;
+use futures::unsync::mpsc::UnboundedSender;
+use tokio_core::reactor::Handle;
+use tokio_timer::Timer;
 use error::*;
 use std::error::Error;
 use xcb;
-use component::Slot;
+use component::{ButtonAction, ClickEvent, ClickHandler, ComponentUpdate, Embedder, ItemContent,
+                ScrollDirection, ScrollEvent, ScrollHandler};
+use ipc::{ItemStateInfo, PanelState};
+
+/// The current id of a running component, shared with the task forwarding
+/// it's updates. `None` once the component has been removed from the bar.
+pub type SharedId = Rc<Cell<Option<usize>>>;
+
+pub type UpdateSender = UnboundedSender<Result<(SharedId, ItemContent)>>;
+
+/// The updates of a running component, shared with the task forwarding them
+/// so that the component can be stopped right away. `None` once it's stopped.
+pub type SharedStream = Rc<RefCell<Option<Box<Stream<Item = ItemContent, Error = ::error::Error>>>>>;
+
+pub type CommandStream = Box<Stream<Item = Command, Error = ::error::Error>>;
+
+pub type InputStream = Box<Stream<Item = Input, Error = ::error::Error>>;
+
+/// A request to change a running bar.
+pub enum Command {
+    /// Applies a new configuration to the bar. See `Bar::reload`.
+    Reload(Config),
+    /// Maps the bar's windows.
+    Show,
+    /// Unmaps the bar's windows.
+    Hide,
+    /// Shows the bar if it's hidden and hides it otherwise.
+    Toggle,
+    /// Replaces the content of a component until it emits a new value.
+    SetText(Target, String),
+    /// Asks a component to update it's value right away.
+    Refresh(Target),
+    /// Sends back the state of every panel.
+    Query(oneshot::Sender<Vec<PanelState>>),
+}
+
+/// Refers to a component of a running bar.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    /// The index of the component, in the order the components were added.
+    Id(usize),
+    /// The name the component was added with.
+    Name(String),
+}
+
+impl FromStr for Target {
+    type Err = ::error::Error;
+
+    /// Numbers are parsed as ids and anything else as a name.
+    fn from_str(s: &str) -> Result<Target> {
+        if s.is_empty() {
+            return Err("missing component".into());
+        }
+        Ok(match s.parse() {
+            Ok(id) => Target::Id(id),
+            Err(_) => Target::Name(s.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Target::Id(id) => write!(f, "{}", id),
+            Target::Name(ref name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Sends commands to the bar it was created for.
+///
+/// A `Controller` is obtained with `BarBuilder::controller` before the bar is built,
+/// so that click handlers and other components can act on the bar, for example
+/// by refreshing the component named "volume" when the volume is scrolled.
+#[derive(Clone)]
+pub struct Controller {
+    sender: UnboundedSender<Command>,
+}
+
+impl Controller {
+    pub fn new(sender: UnboundedSender<Command>) -> Controller {
+        Controller { sender }
+    }
+
+    /// Sends a command to the bar. Commands sent after the bar has stopped are dropped.
+    pub fn send(&self, command: Command) {
+        let _ = self.sender.unbounded_send(command);
+    }
+
+    /// Asks a component to update it's value right away.
+    pub fn refresh<T: Into<Target>>(&self, target: T) {
+        self.send(Command::Refresh(target.into()));
+    }
+
+    /// Shows the bar if it's hidden.
+    pub fn show(&self) {
+        self.send(Command::Show);
+    }
+
+    /// Hides the bar.
+    pub fn hide(&self) {
+        self.send(Command::Hide);
+    }
+
+    /// Shows the bar if it's hidden and hides it otherwise.
+    pub fn toggle(&self) {
+        self.send(Command::Toggle);
+    }
+
+    /// Replaces the content of a component until it emits a new value.
+    pub fn set_text<T: Into<Target>, S: Into<String>>(&self, target: T, text: S) {
+        self.send(Command::SetText(target.into(), text.into()));
+    }
+}
+
+impl From<usize> for Target {
+    fn from(id: usize) -> Target {
+        Target::Id(id)
+    }
+}
+
+impl<'s> From<&'s str> for Target {
+    fn from(name: &'s str) -> Target {
+        Target::Name(name.to_string())
+    }
+}
+
+/// Everything the bar reacts to.
+pub enum Input {
+    /// A new value emitted by a component.
+    Update(SharedId, ItemContent),
+    /// An event received on one of the bar's windows.
+    Event(xcb::GenericEvent),
+    Command(Command),
+    /// The autohide delay of a panel has passed without the pointer entering it.
+    Idle(Window, usize),
+}
+
+/// A component embedding child windows, along with where it has been placed.
+pub struct Embedding {
+    pub embedder: Box<Embedder>,
+    /// The window the embedder is attached to, it's thickness and whether it's vertical.
+    pub window: Option<(Window, u16, bool)>,
+    x: Option<u16>,
+}
+
+impl Embedding {
+    pub fn new(embedder: Box<Embedder>) -> Embedding {
+        Embedding {
+            embedder,
+            window: None,
+            x: None,
+        }
+    }
+}
 
-type UpdateAndEventStream = Merge<UpdateStream, XcbEventStream>;
+/// A component running on the bar. It's stopped when dropped.
+pub struct RunningComponent {
+    /// Ends the task of the component when dropped.
+    pub abort: Option<oneshot::Sender<()>>,
+    pub stream: SharedStream,
+    pub click_handler: Option<ClickHandler>,
+    /// The latest value of the component, used to fill newly created panels.
+    pub content: Option<ItemContent>,
+    pub embedding: Option<Embedding>,
+    pub id: SharedId,
+    /// Identifies the component when the bar is reloaded.
+    pub key: Option<String>,
+    /// Asks the component to update it's value, if it can.
+    pub refresh: Option<UnboundedSender<()>>,
+    pub scroll_handler: Option<ScrollHandler>,
+}
+
+impl RunningComponent {
+    /// Stops the component by dropping it's stream and embedder right away,
+    /// which gives up whatever they hold, like the selection of a tray.
+    /// Updates it has sent already are ignored.
+    pub fn stop(&mut self) {
+        self.id.set(None);
+        self.abort = None;
+        self.stream.borrow_mut().take();
+        if let Some(mut embedding) = self.embedding.take() {
+            if embedding.window.is_some() {
+                embedding.embedder.detach();
+            }
+        }
+    }
+}
+
+impl Drop for RunningComponent {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
 
 /// Struct that contains everything needed to run the bar.
 pub struct Bar {
-    pub center_items: Vec<ItemState>,
+    pub components: Vec<RunningComponent>,
     pub conn: Rc<Connection>,
-    pub foreground: u32,
-    pub geometry: Rectangle,
-    pub item_positions: Vec<(u16, u16)>,
-    pub left_items: Vec<ItemState>,
-    pub right_items: Vec<ItemState>,
-    pub stream: Option<UpdateAndEventStream>,
-    pub window: Window,
-    pub inner_padding: u16,
+    pub handle: Handle,
+    /// Notifies the bar that the autohide delay of a panel has passed.
+    pub idle: UnboundedSender<(Window, usize)>,
+    /// Maps the names of components to their ids.
+    pub names: HashMap<String, usize>,
+    pub outputs: Outputs,
+    pub panels: Vec<Panel>,
+    /// The first event code of the RandR extension.
+    pub randr_base: u8,
+    pub sender: UpdateSender,
+    pub stream: Option<InputStream>,
+    pub template: PanelTemplate,
+    /// Drives the autohide delays and the slides of every panel.
+    pub timer: Timer,
+    /// Whether the panels are shown.
+    pub visible: bool,
+    pub window_conn: Rc<Connection>,
 }
 
 impl Bar {
     /// Returns `self.stream` without borrowing or consuming `self`.
     /// Panics if called twice.
-    fn get_stream(&mut self) -> UpdateAndEventStream {
+    fn get_stream(&mut self) -> InputStream {
         ::std::mem::replace(&mut self.stream, None).unwrap()
     }
 
     /// Launch and run the bar.
     pub fn run(mut self) -> Box<Future<Item = (), Error = ()>> {
+        // Panels collapse after the delay, unless the pointer is already on them
+        if self.visible {
+            for index in 0..self.panels.len() {
+                if !self.panels[index].has_pointer() {
+                    self.schedule_collapse(index);
+                }
+            }
+        }
+
         let future = self.get_stream()
             .map_err(|e| ::error::Error::with_chain(e, ErrorKind::ItemError))
-            .for_each(move |item| -> Result<()> {
-                let (_, update) = match item {
-                    MergedItem::First(update) => (None, Some(update)),
-                    MergedItem::Second(event) => (Some(event), None),
-                    MergedItem::Both(update, event) => (Some(event), Some(update)),
-                };
-
-                if let Some(update) = update {
-                    let size_changed;
-
-                    // Figure out if the component has chanaged
-                    // it's size since the last update.
-                    // TODO: Move to a function
-                    {
-                        let slot_items = match update.slot {
-                            Slot::Left => &mut self.left_items,
-                            Slot::Center => &mut self.center_items,
-                            Slot::Right => &mut self.right_items,
-                        };
-                        slot_items[update.index].update(update.value)?;
-
-                        let width = slot_items[update.index].get_content_width();
-                        size_changed = self.item_positions[update.id].1 != width;
-                    }
-
-                    // Redraw only neccessary stuff
-                    match update.slot {
-                        Slot::Center => self.redraw_center()?,
-                        Slot::Left => self.redraw_left(size_changed, update.index)?,
-                        Slot::Right => self.redraw_right(size_changed, update.index)?,
+            .for_each(move |input| -> Result<()> {
+                match input {
+                    Input::Update(id, value) => {
+                        // Updates of removed components can still be on their way
+                        if let Some(id) = id.get() {
+                            self.update(id, value)?;
+                        }
                     }
+                    Input::Event(event) => self.handle_event(&event)?,
+                    Input::Command(command) => self.execute(command)?,
+                    Input::Idle(window, idle) => self.collapse(window, idle),
                 }
+
+                self.sync_embeddings();
                 Ok(())
             })
             .map_err(|err| {
@@ -83,166 +281,530 @@ impl Bar {
         Box::new(future)
     }
 
-    /// Redraws components in the center slot.
-    /// Unforunately centering means that all components
-    /// must be redrawn if even one of them changes size.
-    fn redraw_center(&mut self) -> Result<()> {
-        // Get future width of all center components
-        let width_all: u16 = self.center_items
-            .iter()
-            .map(|item| item.get_content_width())
-            .sum();
+    /// Displays a new value of a component on every panel.
+    fn update(&mut self, id: usize, value: ItemContent) -> Result<()> {
+        let update = ComponentUpdate {
+            slot: self.template.items[id].slot,
+            id,
+            value,
+        };
+
+        // Every panel shares the same components
+        for panel in &mut self.panels {
+            panel.update(&update)?;
+        }
+        self.components[id].content = Some(update.value);
+        Ok(())
+    }
 
-        // Draw blank background to prevent leftovers after shrinkage
-        // Only does this when component width has shrunk
-        let old_width_all: u16 = self.center_items
-            .iter()
-            .map(|item| self.item_positions[item.get_id()].1)
-            .sum();
-        if width_all < old_width_all {
-            if let Some(first) = self.center_items.first() {
-                let old_start = self.item_positions[first.get_id()].0;
-                self.paint_bg(old_start, old_start + old_width_all)?;
+    /// Executes a command received while running.
+    fn execute(&mut self, command: Command) -> Result<()> {
+        match command {
+            Command::Reload(config) => {
+                // A broken configuration shouldn't bring the bar down
+                let result = config.builder().and_then(|builder| self.reload(builder));
+                if let Err(err) = result {
+                    println!("Failed to reload the configuration: {}", err);
+                }
+            }
+            Command::Show => self.show(),
+            Command::Hide => self.hide(),
+            Command::Toggle => self.toggle(),
+            Command::SetText(target, text) => {
+                match self.resolve(&target) {
+                    Some(id) => self.update(id, ItemContent::Markup(text))?,
+                    None => println!("No component {}", target),
+                }
+            }
+            Command::Refresh(target) => {
+                let id = self.resolve(&target);
+                match id.map(|id| &self.components[id].refresh) {
+                    Some(&Some(ref refresh)) => {
+                        let _ = refresh.unbounded_send(());
+                    }
+                    Some(&None) => println!("Component {} can't be refreshed", target),
+                    None => println!("No component {}", target),
+                }
+            }
+            Command::Query(reply) => {
+                // The requester may have gone away already
+                let _ = reply.send(self.state());
             }
         }
+        Ok(())
+    }
 
-        let mut pos = (self.geometry.width()) / 2 - width_all / 2;
-
-        for item in &self.center_items {
-            self.item_positions[item.get_id()].0 = pos;
-            self.draw_item(item, pos)?;
-            self.item_positions[item.get_id()].1 = item.get_content_width();
-            pos += item.get_content_width();
+    /// Finds the id of a component.
+    pub fn resolve(&self, target: &Target) -> Option<usize> {
+        match *target {
+            Target::Id(id) if id < self.components.len() => Some(id),
+            Target::Id(_) => None,
+            Target::Name(ref name) => self.names.get(name).cloned(),
         }
+    }
 
-        Ok(())
+    /// Shows the bar if it's hidden.
+    pub fn show(&mut self) {
+        self.set_visible(true);
     }
 
-    /// Pretty much the same as `self.redraw_left` but with `left` replaced with `right`.
-    /// The order in which the items are gone through is reversed.
-    fn redraw_right(&mut self, size_changed: bool, index: usize) -> Result<()> {
-        let mut pos = self.geometry.width() - self.inner_padding;
+    /// Hides the bar. The components keep running while the bar is hidden,
+    /// and windows can take the space the bar reserved.
+    pub fn hide(&mut self) {
+        self.set_visible(false);
+    }
 
-        for n in 0..self.right_items.len() {
-            let item = &self.right_items[self.right_items.len() - n - 1];
+    /// Shows the bar if it's hidden and hides it otherwise.
+    pub fn toggle(&mut self) {
+        let visible = !self.visible;
+        self.set_visible(visible);
+    }
 
-            pos -= item.get_content_width();
+    /// Shows or hides every panel, sliding them in or out if the bar slides.
+    fn set_visible(&mut self, visible: bool) {
+        if visible == self.visible {
+            return;
+        }
+        self.visible = visible;
+
+        for index in 0..self.panels.len() {
+            {
+                let panel = &mut self.panels[index];
+                panel.visible = visible;
+                // Panels are shown expanded, as the autohide delay starts over
+                if visible {
+                    panel.collapsed = false;
+                }
+                self.template.update_struts(panel);
+
+                let shown = self.template.shown_area(panel);
+                let hidden = self.template.hidden_area(panel);
+                match self.template.slide {
+                    Some(duration) if visible => {
+                        // Appear out of sight and slide in
+                        panel.set_window_area(&hidden);
+                        panel.set_mapped(true);
+                        let slide = panel::slide(panel, &self.timer, hidden, shown, duration, false);
+                        self.handle.spawn(slide);
+                    }
+                    Some(duration) => {
+                        let slide = panel::slide(panel, &self.timer, shown, hidden, duration, true);
+                        self.handle.spawn(slide);
+                    }
+                    None => panel.set_mapped(visible),
+                }
+            }
 
-            if n < self.right_items.len() - index - 1 {
-                continue;
+            // The pointer isn't on a panel that has just been shown
+            if visible {
+                self.schedule_collapse(index);
             }
+        }
+    }
+
+    /// Collapses a panel once the autohide delay has passed,
+    /// unless the pointer enters or leaves it again in the meantime.
+    fn schedule_collapse(&mut self, index: usize) {
+        let delay = match self.template.autohide {
+            Some(delay) => delay,
+            None => return,
+        };
+
+        let panel = &mut self.panels[index];
+        panel.idle += 1;
+        let idle = (panel.window, panel.idle);
+        let sender = self.idle.clone();
+        let timeout = self.timer
+            .sleep(delay)
+            .map_err(|_| ())
+            .and_then(move |()| sender.unbounded_send(idle).map_err(|_| ()));
+        self.handle.spawn(timeout);
+    }
 
-            if size_changed {
-                let mut bg_start = pos;
-                let bg_end = pos + item.get_content_width();
+    /// Collapses a panel to it's trigger strip, if nothing
+    /// happened since the collapse was scheduled.
+    fn collapse(&mut self, window: Window, idle: usize) {
+        let index = match self.panel_index(window) {
+            Some(index) => index,
+            None => return,
+        };
+
+        let panel = &mut self.panels[index];
+        if panel.idle != idle || panel.collapsed || !self.visible ||
+            self.template.autohide.is_none()
+        {
+            return;
+        }
 
-                if n == self.right_items.len() - 1 {
-                    let old_start = self.item_positions[item.get_id()].0 as u16;
-                    if old_start < bg_start && old_start > 0 {
-                        bg_start = old_start;
-                    }
+        let from = self.template.shown_area(panel);
+        panel.collapsed = true;
+        self.template.update_struts(panel);
+        let to = self.template.shown_area(panel);
+        self.move_panel(index, from, to);
+    }
+
+    /// Expands a collapsed panel and cancels it's pending collapse.
+    fn expand(&mut self, index: usize) {
+        let panel = &mut self.panels[index];
+        panel.idle += 1;
+        if !panel.collapsed {
+            return;
+        }
+
+        let from = self.template.shown_area(panel);
+        panel.collapsed = false;
+        self.template.update_struts(panel);
+        let to = self.template.shown_area(panel);
+        self.move_panel(index, from, to);
+    }
+
+    /// Moves the window of a panel, sliding it if the bar slides.
+    fn move_panel(&self, index: usize, from: Rectangle, to: Rectangle) {
+        let panel = &self.panels[index];
+        match self.template.slide {
+            Some(duration) => {
+                self.handle.spawn(panel::slide(panel, &self.timer, from, to, duration, false))
+            }
+            None => panel.set_window_area(&to),
+        }
+    }
+
+    /// Describes what every panel is currently displaying.
+    pub fn state(&self) -> Vec<PanelState> {
+        self.panels
+            .iter()
+            .map(|panel| {
+                PanelState {
+                    output: panel.output.clone(),
+                    visible: self.visible,
+                    items: panel
+                        .items()
+                        .into_iter()
+                        .map(|item| {
+                            let id = item.get_id();
+                            let (x, width) = panel.item_positions[id];
+                            let content = item.get_state();
+                            ItemStateInfo {
+                                id,
+                                name: self.template.items[id].name.clone(),
+                                key: self.components[id].key.clone(),
+                                slot: self.template.items[id].slot.name().to_string(),
+                                x,
+                                width,
+                                urgent: content.is_urgent(),
+                                markup: content.clone().into_markup(),
+                            }
+                        })
+                        .collect(),
                 }
+            })
+            .collect()
+    }
+
+    /// Applies the settings and components of a builder to the running bar.
+    ///
+    /// The bar keeps it's windows. Components added with a key that matches
+    /// a running component keep running, while the rest of the components
+    /// are stopped and the new ones are started.
+    pub fn reload(&mut self, builder: BarBuilder) -> Result<()> {
+        bar_builder::reload(builder, self)?;
+
+        // Autohide may have been turned on or it's delay changed
+        if self.visible {
+            for index in 0..self.panels.len() {
+                self.schedule_collapse(index);
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the index of the panel owning a window.
+    fn panel_index(&self, window: Window) -> Option<usize> {
+        self.panels.iter().position(|panel| panel.window == window)
+    }
 
-                self.paint_bg(bg_start, bg_end)?;
+    /// Tells embedding components where their items currently are.
+    /// Each of them is attached to the first panel displaying it.
+    fn sync_embeddings(&mut self) {
+        for (id, component) in self.components.iter_mut().enumerate() {
+            let embedding = match component.embedding {
+                Some(ref mut embedding) => embedding,
+                None => continue,
+            };
+
+            let panel = match self.panels.iter().find(|panel| panel.slots[id].is_some()) {
+                Some(panel) => panel,
+                None => continue,
+            };
+
+            let window = (panel.window, panel.thickness(), panel.is_vertical());
+            if embedding.window != Some(window) {
+                embedding.embedder.attach(window.0, window.1, window.2);
+                embedding.window = Some(window);
+                embedding.x = None;
             }
 
-            self.item_positions[item.get_id()].0 = pos;
-            self.item_positions[item.get_id()].1 = item.get_content_width();
-            self.draw_item(item, pos)?;
+            // Child windows go inside the item's box
+            let x = panel.item_positions[id].0 + self.template.items[id].style.inset_left();
+            if embedding.x != Some(x) {
+                embedding.embedder.move_to(x);
+                embedding.x = Some(x);
+            }
+        }
+    }
 
-            if !size_changed {
-                break;
+    /// Takes child windows out of a panel before it's window is destroyed.
+    fn detach_embeddings(&mut self, window: Window) {
+        let embeddings = self.components
+            .iter_mut()
+            .filter_map(|component| component.embedding.as_mut());
+        for embedding in embeddings {
+            if embedding.window.map(|(window, _, _)| window) == Some(window) {
+                embedding.embedder.detach();
+                embedding.window = None;
             }
         }
+    }
 
+    /// Destroys every panel of the bar.
+    pub fn remove_panels(&mut self) {
+        let windows: Vec<Window> = self.panels.iter().map(|panel| panel.window).collect();
+        for window in windows {
+            self.detach_embeddings(window);
+        }
+        self.panels.clear();
+    }
+
+    /// Fills every panel with what the components have emitted so far
+    /// and lays them out again.
+    pub fn refill_panels(&mut self) -> Result<()> {
+        for panel in &mut self.panels {
+            fill_panel(panel, &self.template, &self.components)?;
+            panel.relayout()?;
+        }
         Ok(())
     }
 
-    /// Redraw only needed items in the right slot.
-    /// Symmetric to `self.redraw_left`.
-    ///
-    /// If the component hasn't changed it's size, it doesn't affect
-    /// any other components and we can get away with just painting
-    /// the one component.
+    /// Brings the panels up to date with the current outputs.
     ///
-    /// However if the component has changed it's size, we must also
-    /// redraw every component on the right of it. If the item has shrunk
-    /// we must also repaint the exposed background.
-    fn redraw_left(&mut self, size_changed: bool, index: usize) -> Result<()> {
-        let mut pos = self.inner_padding;
+    /// Panels are moved and resized to fit their output, panels of outputs
+    /// that are gone are destroyed and new outputs get panels of their own.
+    pub fn update_outputs(&mut self) -> Result<()> {
+        let outputs = {
+            let setup = self.conn.get_setup();
+            let screen = setup.roots().next().unwrap();
+            bar_builder::active_outputs(&screen, &self.conn, &self.outputs)?
+        };
+
+        let is_active =
+            |panel: &Panel| outputs.iter().any(|&(ref output, _)| *output == panel.output);
+
+        // Child windows have to be taken out before their window is destroyed
+        let removed: Vec<Window> = self.panels
+            .iter()
+            .filter(|panel| !is_active(panel))
+            .map(|panel| panel.window)
+            .collect();
+        for window in removed {
+            self.detach_embeddings(window);
+        }
 
-        for n in 0..self.left_items.len() {
-            let item = &self.left_items[n];
+        // Destroy the panels of disconnected outputs
+        self.panels.retain(|panel| is_active(panel));
+
+        for (output, area) in outputs {
+            match self.panels.iter().position(|panel| panel.output == output) {
+                Some(index) => self.template.place_panel(&mut self.panels[index], area)?,
+                None => {
+                    let mut panel =
+                        self.template.create_panel(&self.conn, &self.window_conn, output, area)?;
+                    fill_panel(&mut panel, &self.template, &self.components)?;
+                    if !self.visible {
+                        panel.visible = false;
+                        panel.set_mapped(false);
+                        self.template.update_struts(&panel);
+                    }
+                    self.panels.push(panel);
 
-            if n < index {
-                pos += item.get_content_width();
-                continue;
+                    if self.visible {
+                        let index = self.panels.len() - 1;
+                        self.schedule_collapse(index);
+                    }
+                }
             }
+        }
 
-            if size_changed {
-                let bg_start = pos;
-                let mut bg_end = pos + item.get_content_width();
+        self.conn.flush();
+        Ok(())
+    }
 
-                if n == self.left_items.len() - 1 {
-                    let old_end =
-                        self.item_positions[item.get_id()].0 + self.item_positions[item.get_id()].1;
-                    if bg_end < old_end {
-                        bg_end = old_end;
+    /// Dispatches an X event received on one of the bar's windows.
+    fn handle_event(&mut self, event: &xcb::GenericEvent) -> Result<()> {
+        let kind = event.response_type() & !0x80;
+        if kind == self.randr_base + randr::SCREEN_CHANGE_NOTIFY ||
+            kind == self.randr_base + randr::NOTIFY
+        {
+            // Outputs change state one by one, so a failed update keeps the
+            // current panels until the next change
+            if let Err(err) = self.update_outputs() {
+                println!("Failed to update the outputs: {}", err);
+            }
+            return Ok(());
+        }
+
+        match kind {
+            xcb::BUTTON_PRESS | xcb::BUTTON_RELEASE => {
+                let event: &xcb::ButtonPressEvent = unsafe { xcb::cast_event(event) };
+                self.handle_button(event)?;
+            }
+            xcb::EXPOSE => {
+                let event: &xcb::ExposeEvent = unsafe { xcb::cast_event(event) };
+                if let Some(index) = self.panel_index(event.window()) {
+                    let panel = &mut self.panels[index];
+                    panel.add_damage(xcb::Rectangle::new(
+                        event.x() as i16,
+                        event.y() as i16,
+                        event.width(),
+                        event.height(),
+                    ));
+
+                    // A non-zero count means more expose events are following,
+                    // so wait for the rest of the burst before repainting.
+                    if event.count() == 0 {
+                        panel.repaint_damage()?;
                     }
                 }
-
-                self.paint_bg(bg_start, bg_end)?;
             }
+            xcb::ENTER_NOTIFY => {
+                let event: &xcb::EnterNotifyEvent = unsafe { xcb::cast_event(event) };
+                if let Some(index) = self.panel_index(event.event()) {
+                    self.expand(index);
+                }
+            }
+            xcb::LEAVE_NOTIFY => {
+                let event: &xcb::LeaveNotifyEvent = unsafe { xcb::cast_event(event) };
+                // Moving onto an embedded window doesn't leave the panel
+                if event.detail() != xcb::NOTIFY_DETAIL_INFERIOR as u8 {
+                    if let Some(index) = self.panel_index(event.event()) {
+                        self.schedule_collapse(index);
+                    }
+                }
+            }
+            xcb::MAP_NOTIFY => {
+                let event: &xcb::MapNotifyEvent = unsafe { xcb::cast_event(event) };
+                if let Some(index) = self.panel_index(event.window()) {
+                    self.panels[index].damage_all();
+                    self.panels[index].repaint_damage()?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
 
-            self.item_positions[item.get_id()].0 = pos;
-            self.item_positions[item.get_id()].1 = item.get_content_width();
-            self.draw_item(item, pos)?;
+    /// Delivers a button event to the component under the pointer.
+    fn handle_button(&mut self, event: &xcb::ButtonPressEvent) -> Result<()> {
+        if event.event_x() < 0 || event.event_y() < 0 {
+            return Ok(());
+        }
 
-            if !size_changed {
-                break;
+        let (x, y) = (event.event_x() as u16, event.event_y() as u16);
+        let hit = self.panel_index(event.event())
+            .and_then(|index| self.panels[index].item_at(x, y));
+        let (id, x) = match hit {
+            Some(hit) => hit,
+            None => return Ok(()),
+        };
+
+        if let Some(direction) = ScrollDirection::from_button(event.detail()) {
+            // Scrolling only cares about the press half of each notch
+            if event.response_type() & !0x80 == xcb::BUTTON_PRESS {
+                self.handle_scroll(event, direction, id, x)?;
             }
+            return Ok(());
+        }
 
-            pos += item.get_content_width();
+        let action = match event.response_type() & !0x80 {
+            xcb::BUTTON_PRESS => ButtonAction::Press,
+            _ => ButtonAction::Release,
+        };
+
+        if let Some(ref mut handler) = self.components[id].click_handler {
+            handler(ClickEvent {
+                button: event.detail(),
+                action,
+                x,
+                modifiers: event.state(),
+            });
         }
 
         Ok(())
     }
 
-    /// Copies the item's pixmap to the window.
-    fn draw_item(&self, item: &ItemState, pos: u16) -> Result<()> {
-        if !item.is_ready() {
-            return Ok(());
+    /// Delivers a scroll event to the item with the given id.
+    ///
+    /// Notches of the same wheel that are already queued on the connection
+    /// are accumulated into a single event, so that fast scrolling doesn't
+    /// flood the component with one event per notch.
+    fn handle_scroll(
+        &mut self,
+        event: &xcb::ButtonPressEvent,
+        direction: ScrollDirection,
+        id: usize,
+        x: u16,
+    ) -> Result<()> {
+        let mut delta = 1;
+        let mut deferred = None;
+
+        while let Some(next) = self.window_conn.poll_for_queued_event() {
+            let kind = next.response_type() & !0x80;
+            if kind != xcb::BUTTON_PRESS && kind != xcb::BUTTON_RELEASE {
+                deferred = Some(next);
+                break;
+            }
+
+            let detail = unsafe { xcb::cast_event::<xcb::ButtonPressEvent>(&next) }.detail();
+            if detail != event.detail() {
+                deferred = Some(next);
+                break;
+            }
+
+            if kind == xcb::BUTTON_PRESS {
+                delta += 1;
+            }
         }
 
-        try_xcb!(
-            xcb::copy_area_checked,
-            "failed to copy pixmap",
-            &self.conn,
-            item.get_pixmap(),
-            self.window,
-            self.foreground,
-            0,
-            0,
-            pos as i16,
-            0,
-            item.get_content_width() as u16,
-            self.geometry.height()
-        );
+        if let Some(ref mut handler) = self.components[id].scroll_handler {
+            handler(ScrollEvent {
+                direction,
+                delta,
+                x,
+                modifiers: event.state(),
+            });
+        }
 
-        Ok(())
+        // The event that ended the burst still has to be handled
+        match deferred {
+            Some(event) => self.handle_event(&event),
+            None => Ok(()),
+        }
     }
+}
 
-    /// Draws the background starting at point a on the x-axis until point b.
-    fn paint_bg(&self, a: u16, b: u16) -> Result<()> {
-        try_xcb!(
-            xcb::poly_fill_rectangle,
-            "failed to draw background",
-            &self.conn,
-            self.window,
-            self.foreground,
-            &[Rectangle::new(a as i16, 0, b - a, self.geometry.height())]
-        );
-
-        Ok(())
+/// Fills a panel with what the components have emitted so far.
+fn fill_panel(
+    panel: &mut Panel,
+    template: &PanelTemplate,
+    components: &[RunningComponent],
+) -> Result<()> {
+    for (id, component) in components.iter().enumerate() {
+        if let Some(ref value) = component.content {
+            panel.update(&ComponentUpdate {
+                slot: template.items[id].slot,
+                id,
+                value: value.clone(),
+            })?;
+        }
     }
+    Ok(())
 }
@@ -1,42 +1,53 @@
-use bar::Bar;
+use bar::{Bar, Command, CommandStream, Controller, Embedding, Input, InputStream,
+          RunningComponent, SharedStream, UpdateSender};
+use color::Color;
+use panel::{ItemTemplate, PanelTemplate};
+use style::Style;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::rc::Rc;
-use error::{Result, ErrorKind};
-use item_state::ItemState;
+use std::time::Duration;
+use error::{Error, Result, ErrorKind};
 use bar_properties::BarProperties;
-use futures::Stream;
+use futures::{Async, Future, Stream};
+use futures::stream::poll_fn;
+use futures::sync::oneshot;
+use futures::unsync::mpsc;
 use pango::FontDescription;
 use xcb_event_stream::XcbEventStream;
 use tokio_core::reactor::{Core, Handle};
-use component::{Slot, ComponentUpdate, ComponentCreator};
+use tokio_timer::Timer;
+use component::{Slot, ComponentCreator};
 use xcb::{self, Visualtype, Screen, Window, Rectangle, Connection, randr};
 
-#[derive(Clone)]
-/// Defines a color by it's red, green and blue components.
-pub struct Color {
-    pub red: f64,
-    pub green: f64,
-    pub blue: f64,
-}
-
-impl Color {
-    pub fn new(red: f64, green: f64, blue: f64) -> Color {
-        Color { red, green, blue }
-    }
-
-    /// Transforms this struct into a u32, which is in reality C-packed struct
-    /// with the red, green, blue and alpha fields.
-    /// Colors represented in this form are required by XCB.
-    pub fn as_u32(&self) -> u32 {
-        (((255. * self.red).round() as u32) << 16) + (((255. * self.green).round() as u32) << 8) +
-            ((255. * self.blue).round() as u32)
-    }
+/// The visual the bar's windows and pixmaps are created with.
+#[derive(Clone, Copy)]
+pub struct BarVisual {
+    pub depth: u8,
+    pub visualtype: Visualtype,
+    /// A colormap for the visual, if it differs from the root visual.
+    pub colormap: Option<xcb::Colormap>,
 }
 
 /// Bar position relative to the screen.
+///
+/// Bars on the left or right side are vertical. Their `Slot::Left`, `Slot::Center`
+/// and `Slot::Right` items are stacked at the top, center and bottom.
 #[derive(Clone)]
 pub enum Position {
     Top,
     Bottom,
+    Left,
+    Right,
+}
+
+/// How text is displayed on vertical bars.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VerticalText {
+    /// Text is broken into lines fitting the width of the bar.
+    Wrapped,
+    /// Text is turned sideways, reading from the top to the bottom.
+    Rotated,
 }
 
 /// Bar geometry.
@@ -48,7 +59,7 @@ pub enum Geometry {
     Relative {
         /// The bar's position relative to the screen.
         position: Position,
-        /// The bar's height in pixels.
+        /// The bar's height in pixels, or it's width if the bar is vertical.
         height: u16,
         /// Space between the bar and the top or bottom side of the screen in pixels.
         padding_x: u16,
@@ -68,19 +79,48 @@ impl Default for Geometry {
     }
 }
 
-pub type UpdateStream = Box<Stream<Item = ComponentUpdate, Error = ::error::Error>>;
-type Items = Vec<(Slot, Box<ComponentCreator>)>;
+/// Outputs the bar is displayed on.
+#[derive(Clone)]
+pub enum Outputs {
+    /// Only the primary output.
+    Primary,
+    /// Every output in the list, identified by name.
+    Named(Vec<String>),
+    /// Every active output.
+    All,
+}
+
+/// A component together with its slot, style and the output it is restricted to.
+struct Item<'a> {
+    name: Option<String>,
+    slot: Slot,
+    output: Option<&'a str>,
+    style: Style,
+    /// Identifies the component when the bar is reloaded.
+    key: Option<String>,
+    creator: Box<ComponentCreator>,
+}
+
+type Items<'a> = Vec<Item<'a>>;
 
 /// Struct implementing the builder pattern for `Bar`.
 pub struct BarBuilder<'a> {
-    output: Option<&'a str>,
+    outputs: Outputs,
     window_title: String,
     geometry: Geometry,
     bg_color: Color,
     fg_color: Color,
+    accent_color: Option<Color>,
     font_name: String,
-    items: Items,
+    items: Items<'a>,
     inner_padding: u16,
+    reserve_space: bool,
+    vertical_text: VerticalText,
+    autohide: Option<Duration>,
+    slide: Option<Duration>,
+    commands: Option<CommandStream>,
+    controller: Controller,
+    control: Option<mpsc::UnboundedReceiver<Command>>,
 }
 
 /// Implement default for `BarBuilder` because `new()` doesn't require arguments.
@@ -93,15 +133,24 @@ impl<'a> Default for BarBuilder<'a> {
 impl<'a> BarBuilder<'a> {
     /// Create a new `BarBuilder` with default properties.
     pub fn new() -> BarBuilder<'a> {
+        let (sender, control) = mpsc::unbounded();
         BarBuilder {
-            output: None,
+            outputs: Outputs::Primary,
             window_title: String::from("xcbars"),
             geometry: Default::default(),
             bg_color: Color::new(1., 1., 1.),
             fg_color: Color::new(0., 0., 0.),
+            accent_color: None,
             items: vec![],
             font_name: String::new(),
             inner_padding: 0,
+            reserve_space: true,
+            vertical_text: VerticalText::Wrapped,
+            autohide: None,
+            slide: None,
+            commands: None,
+            controller: Controller::new(sender),
+            control: Some(control),
         }
     }
 
@@ -114,17 +163,109 @@ impl<'a> BarBuilder<'a> {
     }
 
     /// Adds a component to the bar into the specified slot.
-    pub fn add_component<C>(mut self, slot: Slot, component: C) -> Self
+    pub fn add_component<C>(self, slot: Slot, component: C) -> Self
+    where
+        C: ComponentCreator + 'static,
+    {
+        self.add_keyed_component(None, None, slot, Style::default(), component)
+    }
+
+    /// Adds a component with a name, which can be used to refer to it
+    /// while the bar is running. See `name`.
+    pub fn add_named_component<S, C>(self, name: S, slot: Slot, component: C) -> Self
+    where
+        S: Into<String>,
+        C: ComponentCreator + 'static,
+    {
+        self.add_component(slot, component).name(name)
+    }
+
+    /// Names the component added last.
+    ///
+    /// Named components can be referred to with a `Target` in commands,
+    /// for example from the control socket or a `Controller`.
+    /// Names have to be unique. Does nothing if no component has been added yet.
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        if let Some(item) = self.items.last_mut() {
+            item.name = Some(name.into());
+        }
+        self
+    }
+
+    /// Adds a component to the bar into the specified slot
+    /// and displays it with the given style.
+    pub fn add_styled_component<C>(self, slot: Slot, style: Style, component: C) -> Self
     where
         C: ComponentCreator + 'static,
     {
-        self.items.push((slot, Box::new(component)));
+        self.add_keyed_component(None, None, slot, style, component)
+    }
+
+    /// Adds a component that is only displayed on the specified output.
+    ///
+    /// This is useful for components that only make sense once,
+    /// such as a system tray on the primary output.
+    pub fn add_component_on<C>(self, output: &'a str, slot: Slot, component: C) -> Self
+    where
+        C: ComponentCreator + 'static,
+    {
+        self.add_keyed_component(None, Some(output), slot, Style::default(), component)
+    }
+
+    /// Adds a component that is only displayed on the specified output
+    /// and displays it with the given style.
+    pub fn add_styled_component_on<C>(
+        self,
+        output: &'a str,
+        slot: Slot,
+        style: Style,
+        component: C,
+    ) -> Self
+    where
+        C: ComponentCreator + 'static,
+    {
+        self.add_keyed_component(None, Some(output), slot, style, component)
+    }
+
+    /// Adds a component identified by a key.
+    ///
+    /// When a running bar is reloaded with `Bar::reload`, a component
+    /// with the same key as one already running on the bar is not started again.
+    /// The running component is kept along with it's latest value instead.
+    pub fn add_keyed_component<C>(
+        mut self,
+        key: Option<String>,
+        output: Option<&'a str>,
+        slot: Slot,
+        style: Style,
+        component: C,
+    ) -> Self
+    where
+        C: ComponentCreator + 'static,
+    {
+        self.items.push(Item {
+            name: None,
+            slot,
+            output,
+            style,
+            key,
+            creator: Box::new(component),
+        });
         self
     }
 
     /// Set the output you want the bar to be displayed on.
     pub fn output(mut self, output: &'a str) -> Self {
-        self.output = Some(output);
+        self.outputs = Outputs::Named(vec![output.to_string()]);
+        self
+    }
+
+    /// Set the outputs you want the bar to be displayed on.
+    ///
+    /// A separate bar window is created for each of the outputs,
+    /// but the components are shared between them.
+    pub fn outputs(mut self, outputs: Outputs) -> Self {
+        self.outputs = outputs;
         self
     }
 
@@ -152,140 +293,495 @@ impl<'a> BarBuilder<'a> {
         self
     }
 
+    /// Set the accent color, used for drawing urgent items.
+    /// Defaults to the foreground color.
+    pub fn accent(mut self, color: Color) -> Self {
+        self.accent_color = Some(color);
+        self
+    }
+
     /// Set the default background color.
     pub fn background(mut self, color: Color) -> Self {
         self.bg_color = color;
         self
     }
 
+    /// Set whether the window manager should reserve space for the bar.
+    ///
+    /// Disable this for bars that should be drawn on top of other windows.
+    pub fn reserve_space(mut self, reserve_space: bool) -> Self {
+        self.reserve_space = reserve_space;
+        self
+    }
+
+    /// Set how text is displayed if the bar is vertical.
+    pub fn vertical_text(mut self, vertical_text: VerticalText) -> Self {
+        self.vertical_text = vertical_text;
+        self
+    }
+
+    /// Set the bar to collapse to a thin strip at the edge of the screen
+    /// once the pointer has been away from it for `delay`.
+    ///
+    /// The bar expands again when the pointer enters the strip,
+    /// and doesn't reserve any space while it's collapsed.
+    pub fn autohide(mut self, delay: Duration) -> Self {
+        self.autohide = Some(delay);
+        self
+    }
+
+    /// Set the bar to slide in and out of the edge of the screen
+    /// over `duration` when it's shown or hidden.
+    pub fn slide(mut self, duration: Duration) -> Self {
+        self.slide = Some(duration);
+        self
+    }
+
     /// Set the title of the window.
     pub fn window_title<T: Into<String>>(mut self, window_title: T) -> Self {
         self.window_title = window_title.into();
         self
     }
 
+    /// Set a stream of commands to be executed by the running bar.
+    pub fn commands<S>(mut self, commands: S) -> Self
+    where
+        S: Stream<Item = Command, Error = ::error::Error> + 'static,
+    {
+        self.commands = Some(Box::new(commands));
+        self
+    }
+
+    /// Returns a `Controller` for sending commands to the bar once it's running.
+    ///
+    /// Controllers only work with the bar built from this builder. The controllers
+    /// of a builder passed to `Bar::reload` never reach the bar.
+    pub fn controller(&self) -> Controller {
+        self.controller.clone()
+    }
+
+    /// Whether the bar needs a visual with an alpha channel.
+    fn is_translucent(&self) -> bool {
+        !self.bg_color.is_opaque() ||
+            self.items.iter().any(|item| {
+                item.style.background.as_ref().map_or(false, |color| !color.is_opaque())
+            })
+    }
+
     /// Consumes and splits self into `self.items` and `BarProperties` struct,
     /// containing everything else relevant.
-    fn into_items_and_props(self, area: &Rectangle) -> (Items, BarProperties) {
+    fn into_items_and_props(self) -> (Items<'a>, BarProperties) {
         let props = BarProperties {
             geometry: self.geometry,
-            area: *area,
-            accent_color: None,
+            area: Rectangle::new(0, 0, 0, 0),
+            accent_color: self.accent_color,
             fg_color: self.fg_color,
             bg_color: self.bg_color,
             font: FontDescription::from_string(&*self.font_name),
+            vertical_text: self.vertical_text,
         };
         (self.items, props)
     }
 
     /// Builds and returns the bar.
-    pub fn build(self, handle: Handle) -> Result<Bar> {
+    pub fn build(mut self, handle: Handle) -> Result<Bar> {
         let (window_conn, _) = Connection::connect(None).map_err(ErrorKind::XcbConnection)?;
         let (conn, _) = Connection::connect(None).map_err(ErrorKind::XcbConnection)?;
 
-        let geometry;
-        let window;
-        let visualtype;
-        let foreground = conn.generate_id();
+        let outputs;
+        let visual;
+        let foreground;
+
+        // A translucent background requires a visual with an alpha channel
+        let translucent = self.is_translucent();
 
         // Scope for `screen`
         {
             let setup = conn.get_setup();
             let screen = setup.roots().next().unwrap();
 
-            geometry = calculate_geometry(&screen, &self.geometry, &self.output, &conn)?;
-            window = create_window(
+            outputs = resolve_outputs(&screen, &conn, &self.outputs)?;
+            visual = find_visual(&screen, &conn, translucent)?;
+            foreground = create_gc(&screen, &conn, &visual, &self.bg_color)?;
+
+            // Get notified about outputs being changed, added or removed
+            randr::select_input(
                 &window_conn,
-                &screen,
-                &geometry,
-                self.bg_color.as_u32(),
-                self.window_title.as_bytes(),
-            )?;
-            visualtype = find_visualtype(&screen).unwrap();
-
-            // Create xcb graphics context for drawin te background
-            try_xcb!(
-                xcb::create_gc_checked,
-                "failed to create gcontext",
-                &conn,
-                foreground,
                 screen.root(),
-                &[
-                    (xcb::GC_FOREGROUND, self.bg_color.as_u32()),
-                    (xcb::GC_GRAPHICS_EXPOSURES, 0)
-                ]
+                (randr::NOTIFY_MASK_SCREEN_CHANGE | randr::NOTIFY_MASK_CRTC_CHANGE |
+                     randr::NOTIFY_MASK_OUTPUT_CHANGE) as u16,
             );
         }
 
-        let conn = Rc::new(conn);
+        let randr_base = window_conn
+            .get_extension_data(&mut randr::id())
+            .ok_or("RandR extension is not available")?
+            .first_event();
 
-        let item_count = self.items.len();
-        let mut left_items = vec![];
-        let mut center_items = vec![];
-        let mut right_items = vec![];
-        let mut updates: Option<UpdateStream> = None;
+        let conn = Rc::new(conn);
+        let commands = self.commands.take();
+        let control = self.control.take().unwrap();
+        let names = self.names()?;
 
-        // Store inner padding before consumption
-        let inner_padding = self.inner_padding;
+        // Store settings needed by panels before consumption
+        let output_selection = self.outputs.clone();
 
         // Consumes self
-        let (items, properties) = self.into_items_and_props(&geometry);
-        let properties = Rc::new(properties);
-
-        // Initiate components and convert them into a stream of
-        // updates.  The sream also carries information about the
-        // source component such as the id, slot and the index of
-        // the component in the said slot.
-        for (id, (slot, creator)) in items.into_iter().enumerate() {
-            let vec = match slot {
-                Slot::Left => &mut left_items,
-                Slot::Center => &mut center_items,
-                Slot::Right => &mut right_items,
-            };
-            let index = vec.len();
+        let (template, items) = self.into_template(foreground, visual);
 
-            vec.push(ItemState::new(
-                id,
-                properties.clone(),
-                0,
-                visualtype,
-                conn.clone(),
-                window,
-            ));
-
-            let stream = creator.create(handle.clone())?.map(move |value| {
-                ComponentUpdate {
-                    slot: slot,
-                    index,
-                    id,
-                    value,
+        // Create a panel on every output
+        let panels = outputs
+            .into_iter()
+            .map(|(output, area)| template.create_panel(&conn, &window_conn, output, area))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Every component runs in a task of it's own, which forwards
+        // the updates of the component to the bar through a channel.
+        let (sender, receiver) = mpsc::unbounded();
+        let components = items
+            .into_iter()
+            .enumerate()
+            .map(|(id, item)| start_component(item, id, &handle, &sender))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Join the component updates with events from XCB and the commands
+        let window_conn = Rc::new(window_conn);
+        let updates = receiver
+            .map_err(|()| "channel error".into())
+            .and_then(|update| update)
+            .map(|(id, value)| Input::Update(id, value));
+        let events = XcbEventStream::new(window_conn.clone(), &handle)?.map(Input::Event);
+        let control = control
+            .map_err(|()| "channel error".into())
+            .map(Input::Command);
+        let (idle, idle_receiver) = mpsc::unbounded();
+        let idle_receiver = idle_receiver
+            .map_err(|()| "channel error".into())
+            .map(|(window, idle)| Input::Idle(window, idle));
+        let mut stream: InputStream = Box::new(
+            updates.select(events).select(control).select(idle_receiver),
+        );
+        if let Some(commands) = commands {
+            stream = Box::new(stream.select(commands.map(Input::Command)));
+        }
+
+        Ok(Bar {
+            components,
+            conn,
+            handle,
+            idle,
+            names,
+            outputs: output_selection,
+            panels,
+            randr_base,
+            sender,
+            stream: Some(stream),
+            template,
+            timer: Timer::default(),
+            visible: true,
+            window_conn,
+        })
+    }
+
+    /// Maps the names of the components to their ids.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if two components have the same name.
+    fn names(&self) -> Result<HashMap<String, usize>> {
+        let mut names = HashMap::new();
+        for (id, item) in self.items.iter().enumerate() {
+            if let Some(ref name) = item.name {
+                if names.insert(name.clone(), id).is_some() {
+                    return Err(ErrorKind::DuplicateName(name.clone()).into());
                 }
-            });
-            updates = match updates {
-                None => Some(Box::new(stream)),
-                Some(other) => Some(Box::new(other.select(stream))),
             }
         }
+        Ok(names)
+    }
+
+    /// Consumes self and splits it into the template of the panels and the components.
+    fn into_template(self, foreground: u32, visual: BarVisual) -> (PanelTemplate, Items<'a>) {
+        let geometry = self.geometry.clone();
+        let inner_padding = self.inner_padding;
+        let reserve_space = self.reserve_space;
+        let autohide = self.autohide;
+        let slide = self.slide;
+        let window_title = self.window_title.clone();
 
-        // Join the component update stream with
-        // a stream carrying events from XCB.
-        let stream = updates
-            .unwrap_or_else(|| Box::new(::futures::stream::empty()))
-            .merge(XcbEventStream::new(window_conn, &handle)?);
+        let (items, properties) = self.into_items_and_props();
 
-        Ok(Bar {
-            center_items,
-            conn,
+        let template = PanelTemplate {
             foreground,
             geometry,
-            item_positions: vec![(0, 0); item_count],
-            left_items,
-            right_items,
-            stream: Some(stream),
-            window,
             inner_padding,
+            items: items
+                .iter()
+                .map(|item| {
+                    ItemTemplate {
+                        name: item.name.clone(),
+                        slot: item.slot,
+                        output: item.output.map(String::from),
+                        style: Rc::new(item.style.clone()),
+                    }
+                })
+                .collect(),
+            properties,
+            reserve_space,
+            autohide,
+            slide,
+            visual,
+            window_title,
+        };
+
+        (template, items)
+    }
+}
+
+/// Applies the settings and components of a builder to a running bar.
+///
+/// The windows of the bar are kept, unless the bar switches between
+/// being opaque and translucent, which requires windows with a different visual.
+/// Running components are matched to the new ones by their key and
+/// only the components that aren't already running are started.
+///
+/// If a new component fails to start, the bar keeps it's old settings and items,
+/// although the components that were being replaced stay stopped.
+pub fn reload(builder: BarBuilder, bar: &mut Bar) -> Result<()> {
+    let names = builder.names()?;
+    let outputs = builder.outputs.clone();
+    let background = builder.bg_color.clone();
+    let conn = bar.conn.clone();
+
+    // A new visual needs a graphics context of it's own,
+    // which replaces the old one once the components are running
+    let translucent = builder.is_translucent();
+    let new_visual = if translucent != bar.template.visual.colormap.is_some() {
+        let setup = conn.get_setup();
+        let screen = setup.roots().next().unwrap();
+        let visual = find_visual(&screen, &conn, translucent)?;
+        let foreground = create_gc(&screen, &conn, &visual, &background)?;
+        Some((visual, foreground))
+    } else {
+        None
+    };
+
+    let (visual, foreground) = new_visual.unwrap_or((bar.template.visual, bar.template.foreground));
+    let (template, items) = builder.into_template(foreground, visual);
+
+    let components = match restart_components(items, &mut bar.components, &bar.handle, &bar.sender) {
+        Ok(components) => components,
+        Err(err) => {
+            if new_visual.is_some() {
+                xcb::free_gc(&conn, foreground);
+            }
+            return Err(err);
+        }
+    };
+
+    // Everything that can fail has succeeded, so the new state replaces the old one
+    if new_visual.is_some() {
+        // Every panel is created again for a new visual
+        bar.remove_panels();
+        xcb::free_gc(&conn, bar.template.foreground);
+    } else {
+        xcb::change_gc(&conn, foreground, &[(xcb::GC_FOREGROUND, background.as_u32())]);
+    }
+    bar.outputs = outputs;
+    bar.names = names;
+    bar.components = components;
+    bar.template = template;
+
+    // Fill the kept panels with the new items
+    for panel in &mut bar.panels {
+        bar.template.add_items(panel);
+        set_title(&bar.conn, panel.window, bar.template.window_title.as_bytes());
+    }
+    bar.refill_panels()?;
+
+    // Place the kept panels and create the panels of new outputs
+    bar.update_outputs()
+}
+
+/// Starts the components of the items, reusing the running components with a matching key.
+/// Returns the components in the order of the items, taking the reused ones out of `running`.
+///
+/// Running components that aren't reused are stopped first, so that they give up
+/// resources only one instance can hold, like the tray selection.
+/// If a component fails to start, `running` is left as it was apart from that.
+fn restart_components(
+    items: Items,
+    running: &mut Vec<RunningComponent>,
+    handle: &Handle,
+    sender: &UpdateSender,
+) -> Result<Vec<RunningComponent>> {
+    let mut reused: Vec<Option<usize>> = vec![];
+    let mut claimed = vec![false; running.len()];
+    for item in &items {
+        let index = item.key.as_ref().and_then(|key| {
+            running.iter().zip(&claimed).position(|(old, &claimed)| {
+                !claimed && old.key.as_ref() == Some(key)
+            })
+        });
+        if let Some(index) = index {
+            claimed[index] = true;
+        }
+        reused.push(index);
+    }
+
+    for (component, _) in running.iter_mut().zip(&claimed).filter(|&(_, &claimed)| !claimed) {
+        component.stop();
+    }
+
+    // Started components are stopped again if a later one fails
+    let mut started = vec![];
+    for (id, (item, reused)) in items.into_iter().zip(&reused).enumerate() {
+        started.push(match *reused {
+            Some(_) => None,
+            None => Some(start_component(item, id, handle, sender)?),
+        });
+    }
+
+    // Whatever isn't reused is dropped with `old`
+    let mut old: Vec<Option<RunningComponent>> = running.drain(..).map(Some).collect();
+    let components = started
+        .into_iter()
+        .zip(reused)
+        .enumerate()
+        .map(|(id, (started, reused))| match reused {
+            Some(index) => {
+                let component = old[index].take().unwrap();
+                component.id.set(Some(id));
+                component
+            }
+            None => started.unwrap(),
         })
+        .collect();
+    Ok(components)
+}
+
+/// Initializes a component and spawns a task forwarding it's updates to the bar.
+fn start_component(
+    item: Item,
+    id: usize,
+    handle: &Handle,
+    sender: &UpdateSender,
+) -> Result<RunningComponent> {
+    let mut creator = item.creator;
+    creator.init()?;
+    let click_handler = creator.on_click();
+    let scroll_handler = creator.on_scroll();
+    let embedding = creator.embedder().map(Embedding::new);
+
+    let shared_id = Rc::new(Cell::new(Some(id)));
+    let (abort, aborted) = oneshot::channel();
+
+    let forward_id = shared_id.clone();
+    let sender = sender.clone();
+    // Refreshes are passed to the stream of the component through a channel
+    let (refresh, stream) = if creator.refreshable() {
+        let (refresh, refreshes) = mpsc::unbounded();
+        let refreshes = refreshes.map_err(|()| Error::from("channel error"));
+        (Some(refresh), creator.into_refreshed_stream(handle.clone(), Box::new(refreshes)))
+    } else {
+        (None, creator.into_stream(handle.clone()))
+    };
+
+    // The task only holds on to the stream through the component
+    let shared_stream: SharedStream = Rc::new(RefCell::new(Some(stream)));
+    let polled = shared_stream.clone();
+    let stream = poll_fn(move || match *polled.borrow_mut() {
+        Some(ref mut stream) => stream.poll(),
+        None => Ok(Async::Ready(None)),
+    });
+
+    let forward = stream
+        .then(move |value| Ok::<_, ()>(value.map(|value| (forward_id.clone(), value))))
+        .for_each(move |update| sender.unbounded_send(update).map_err(|_| ()));
+
+    // Dropping the abort handle stops the task
+    handle.spawn(
+        forward
+            .select(aborted.then(|_| Ok::<(), ()>(())))
+            .map(|_| ())
+            .map_err(|_| ()),
+    );
+
+    Ok(RunningComponent {
+        abort: Some(abort),
+        stream: shared_stream,
+        click_handler,
+        content: None,
+        embedding,
+        id: shared_id,
+        key: item.key,
+        refresh,
+        scroll_handler,
+    })
+}
+
+/// Creates the graphics context used for drawing the background.
+fn create_gc<'s>(
+    screen: &Screen<'s>,
+    conn: &Connection,
+    visual: &BarVisual,
+    background: &Color,
+) -> Result<u32> {
+    let foreground = conn.generate_id();
+
+    // The graphics context has to be created on a drawable with the
+    // same depth as the windows, which the root window might not have.
+    let drawable = conn.generate_id();
+    xcb::create_pixmap(conn, visual.depth, drawable, screen.root(), 1, 1);
+
+    // Create xcb graphics context for drawin te background
+    try_xcb!(
+        xcb::create_gc_checked,
+        "failed to create gcontext",
+        conn,
+        foreground,
+        drawable,
+        &[
+            (xcb::GC_FOREGROUND, background.as_u32()),
+            (xcb::GC_GRAPHICS_EXPOSURES, 0)
+        ]
+    );
+    xcb::free_pixmap(conn, drawable);
+
+    Ok(foreground)
+}
+
+/// Finds the visual for the bar's windows.
+///
+/// If the bar is translucent a 32-bit TrueColor visual is used, along with
+/// a colormap created for it. Otherwise the root visual is used.
+fn find_visual<'s>(screen: &Screen<'s>, conn: &Connection, translucent: bool) -> Result<BarVisual> {
+    if translucent {
+        if let Some(visualtype) = find_argb_visualtype(screen) {
+            let colormap = conn.generate_id();
+            try_xcb!(
+                xcb::create_colormap_checked,
+                "failed to create colormap",
+                conn,
+                xcb::COLORMAP_ALLOC_NONE as u8,
+                colormap,
+                screen.root(),
+                visualtype.visual_id()
+            );
+
+            return Ok(BarVisual {
+                depth: 32,
+                visualtype,
+                colormap: Some(colormap),
+            });
+        }
     }
+
+    Ok(BarVisual {
+        depth: screen.root_depth(),
+        visualtype: find_visualtype(screen).ok_or("Unable to find the root visual")?,
+        colormap: None,
+    })
 }
 
 /// Finds a visual type matching the one of the screen provided.
@@ -300,22 +796,28 @@ fn find_visualtype<'s>(screen: &Screen<'s>) -> Option<Visualtype> {
     None
 }
 
+/// Finds a 32-bit visual type with an alpha channel.
+fn find_argb_visualtype<'s>(screen: &Screen<'s>) -> Option<Visualtype> {
+    for depth in screen.allowed_depths().filter(|depth| depth.depth() == 32) {
+        for visual in depth.visuals() {
+            if visual.class() == xcb::VISUAL_CLASS_TRUE_COLOR as u8 {
+                return Some(visual);
+            }
+        }
+    }
+    None
+}
+
 /// Calculates the position and size of the bar on
-/// screen given the Geometry struct and screen dimensions.
-fn calculate_geometry<'s>(
-    screen: &Screen<'s>,
-    geometry: &Geometry,
-    output: &Option<&str>,
-    conn: &Connection,
-) -> Result<Rectangle> {
-    let screen_info = get_screen_info(screen, conn, output)?;
-    let screen_w = screen_info.width();
-    let screen_h = screen_info.height();
-    let x_offset = screen_info.x();
-    let y_offset = screen_info.y();
+/// screen given the Geometry struct and the area of the output.
+pub fn calculate_geometry(output: &Rectangle, geometry: &Geometry) -> Rectangle {
+    let screen_w = output.width();
+    let screen_h = output.height();
+    let x_offset = output.x();
+    let y_offset = output.y();
 
     match *geometry {
-        Geometry::Absolute(ref rect) => Ok(*rect),
+        Geometry::Absolute(ref rect) => *rect,
         Geometry::Relative {
             ref position,
             height: bar_height,
@@ -340,24 +842,148 @@ fn calculate_geometry<'s>(
                     width = screen_w - 2 * padding_x;
                     height = bar_height;
                 }
+                Position::Left => {
+                    x = x_offset + padding_x as i16;
+                    y = y_offset + padding_y as i16;
+                    width = bar_height;
+                    height = screen_h - 2 * padding_y;
+                }
+                Position::Right => {
+                    x = x_offset + (screen_w - bar_height - padding_x) as i16;
+                    y = y_offset + padding_y as i16;
+                    width = bar_height;
+                    height = screen_h - 2 * padding_y;
+                }
             };
 
-            Ok(Rectangle::new(x, y, width, height))
+            Rectangle::new(x, y, width, height)
+        }
+    }
+}
+
+/// Whether a bar occupying `area` is vertical, stacking it's items from the top down.
+pub fn is_vertical(area: &Rectangle) -> bool {
+    area.height() > area.width()
+}
+
+/// Figures out which edge of the output the bar is docked to.
+/// Absolute geometries are docked to the edge closest to the bar,
+/// which is the left or right edge for vertical bars.
+pub fn docked_position(geometry: &Geometry, area: &Rectangle, output: &Rectangle) -> Position {
+    match *geometry {
+        Geometry::Relative { ref position, .. } => position.clone(),
+        Geometry::Absolute(_) if is_vertical(area) => {
+            let bar_center = area.x() as i32 + area.width() as i32 / 2;
+            let output_center = output.x() as i32 + output.width() as i32 / 2;
+            if bar_center <= output_center {
+                Position::Left
+            } else {
+                Position::Right
+            }
+        }
+        Geometry::Absolute(_) => {
+            let bar_center = area.y() as i32 + area.height() as i32 / 2;
+            let output_center = output.y() as i32 + output.height() as i32 / 2;
+            if bar_center <= output_center {
+                Position::Top
+            } else {
+                Position::Bottom
+            }
+        }
+    }
+}
+
+/// Calculates the values of `_NET_WM_STRUT_PARTIAL` for a bar occupying `area`.
+///
+/// Struts are measured from the edges of the root window, which spans every
+/// output, so the reserved space depends on where the output is placed.
+pub fn calculate_struts<'s>(
+    screen: &Screen<'s>,
+    geometry: &Geometry,
+    area: &Rectangle,
+    output: &Rectangle,
+) -> [u32; 12] {
+    let mut strut = [0; 12];
+
+    let start_x = area.x().max(0) as u32;
+    let end_x = (area.x() as i32 + area.width() as i32 - 1).max(0) as u32;
+    let start_y = area.y().max(0) as u32;
+    let end_y = (area.y() as i32 + area.height() as i32 - 1).max(0) as u32;
+
+    match docked_position(geometry, area, output) {
+        Position::Top => {
+            strut[2] = (area.y() as i32 + area.height() as i32).max(0) as u32;
+            strut[8] = start_x;
+            strut[9] = end_x;
         }
+        Position::Bottom => {
+            let root_height = screen.height_in_pixels() as i32;
+            strut[3] = (root_height - area.y() as i32).max(0) as u32;
+            strut[10] = start_x;
+            strut[11] = end_x;
+        }
+        Position::Left => {
+            strut[0] = (area.x() as i32 + area.width() as i32).max(0) as u32;
+            strut[4] = start_y;
+            strut[5] = end_y;
+        }
+        Position::Right => {
+            let root_width = screen.width_in_pixels() as i32;
+            strut[1] = (root_width - area.x() as i32).max(0) as u32;
+            strut[6] = start_y;
+            strut[7] = end_y;
+        }
+    }
+
+    strut
+}
+
+/// Finds the name and area of every output the bar should be displayed on.
+/// Fails if the primary output or any of the named outputs can't be found.
+fn resolve_outputs<'s>(
+    screen: &Screen<'s>,
+    conn: &Connection,
+    outputs: &Outputs,
+) -> Result<Vec<(String, Rectangle)>> {
+    let active = active_outputs(screen, conn, outputs)?;
+
+    match *outputs {
+        Outputs::Primary if active.is_empty() => {
+            return Err("Unable to find the primary output.".into());
+        }
+        Outputs::Named(ref names) => {
+            for name in names {
+                if !active.iter().any(|&(ref output_name, _)| output_name == name) {
+                    return Err(["Unable to find output ", name.as_str()].concat().into());
+                }
+            }
+        }
+        _ => {}
     }
+
+    Ok(active)
 }
 
-// Get informatio about specified output
-fn get_screen_info<'s>(
+/// Finds the name and area of every currently active output
+/// the bar should be displayed on.
+pub fn active_outputs<'s>(
     screen: &Screen<'s>,
     conn: &Connection,
-    query_output_name: &Option<&str>,
-) -> Result<xcb::Reply<xcb::ffi::randr::xcb_randr_get_crtc_info_reply_t>> {
-    if query_output_name.is_none() {
-        return get_primary_screen_info(screen, conn);
+    outputs: &Outputs,
+) -> Result<Vec<(String, Rectangle)>> {
+    match *outputs {
+        Outputs::Primary => Ok(get_primary_output(screen, conn)?.into_iter().collect()),
+        Outputs::All => get_outputs(screen, conn),
+        Outputs::Named(ref names) => {
+            let mut outputs = get_outputs(screen, conn)?;
+            outputs.retain(|&(ref output_name, _)| names.contains(output_name));
+            Ok(outputs)
+        }
     }
-    let query_output_name = query_output_name.unwrap();
+}
 
+/// Get the name and area of every active output.
+fn get_outputs<'s>(screen: &Screen<'s>, conn: &Connection) -> Result<Vec<(String, Rectangle)>> {
     // Load screen resources of the root window
     // Return result on error
     let res_cookie = randr::get_screen_resources(conn, screen.root());
@@ -365,6 +991,8 @@ fn get_screen_info<'s>(
         .get_reply()
         .map_err(|_| "Unable to get screen resources")?;
 
+    let mut outputs = vec![];
+
     // Get all crtcs from the reply
     let crtcs = res_reply.crtcs();
 
@@ -390,21 +1018,19 @@ fn get_screen_info<'s>(
                 output_name = String::from_utf8_lossy(output_info_reply.name()).into();
             }
 
-            // If the output name is the requested name, return the dimensions
-            if output_name == query_output_name {
-                return Ok(reply);
-            }
+            let area = Rectangle::new(reply.x(), reply.y(), reply.width(), reply.height());
+            outputs.push((output_name, area));
         }
     }
-    let error_msg = ["Unable to find output ", query_output_name].concat();
-    Err(error_msg.into())
+
+    Ok(outputs)
 }
 
-/// Get information about the primary output
-fn get_primary_screen_info<'s>(
+/// Get the name and area of the primary output, if there is one with a crtc.
+fn get_primary_output<'s>(
     screen: &Screen<'s>,
     conn: &Connection,
-) -> Result<xcb::Reply<xcb::ffi::randr::xcb_randr_get_crtc_info_reply_t>> {
+) -> Result<Option<(String, Rectangle)>> {
     // Load primary output
     let output_cookie = randr::get_output_primary(conn, screen.root());
     let output_reply = output_cookie
@@ -412,18 +1038,34 @@ fn get_primary_screen_info<'s>(
         .map_err(|_| "Unable to get primary output.")?;
     let output = output_reply.output();
 
+    // While outputs are plugged in or out there may be no primary output for a moment
+    if output == xcb::NONE {
+        return Ok(None);
+    }
+
     // Get crtc of primary output
     let output_info_cookie = randr::get_output_info(conn, output, 0);
     let output_info_reply = output_info_cookie
         .get_reply()
         .map_err(|_| "Unable to get info about primary output")?;
     let crtc = output_info_reply.crtc();
+    let name = String::from_utf8_lossy(output_info_reply.name()).into();
+    if crtc == xcb::NONE {
+        return Ok(None);
+    }
 
     // Get info of primary output's crtc
     let crtc_info_cookie = randr::get_crtc_info(conn, crtc, 0);
-    Ok(crtc_info_cookie
+    let reply = crtc_info_cookie
         .get_reply()
-        .map_err(|_| "Unable to get crtc from primary output")?)
+        .map_err(|_| "Unable to get crtc from primary output")?;
+
+    if reply.width() == 0 {
+        return Ok(None);
+    }
+
+    let area = Rectangle::new(reply.x(), reply.y(), reply.width(), reply.height());
+    Ok(Some((name, area)))
 }
 
 /// Convinience macro for setting window properites.
@@ -457,19 +1099,53 @@ macro_rules! set_prop {
     };
 }
 
+/// Sets the struts of the window, telling the WM how much space to reserve for it.
+pub fn set_struts(conn: &Connection, window: Window, strut: &[u32; 12]) {
+    set_prop!(conn, window, "_NET_WM_STRUT_PARTIAL", &strut[..]);
+    set_prop!(conn, window, "_NET_WM_STRUT", &strut[0..4]);
+}
+
+/// Sets the title of the window.
+pub fn set_title(conn: &Connection, window: Window, window_title: &[u8]) {
+    set_prop!(conn, window, "_NET_WM_NAME", window_title, "UTF8_STRING", 8);
+    set_prop!(conn, window, "WM_NAME", window_title, "STRING", 8);
+}
+
 /// Creates a Xorg window using XCB.
-fn create_window<'s>(
+pub fn create_window<'s>(
     conn: &Connection,
     screen: &Screen<'s>,
     geometry: &Rectangle,
+    strut: &[u32; 12],
     background: u32,
+    visual: &BarVisual,
     window_title: &[u8],
 ) -> Result<Window> {
     let window = conn.generate_id();
 
+    let mut values = vec![
+        (xcb::CW_BACK_PIXEL, background), // Default background color
+        (
+            xcb::CW_EVENT_MASK, // What kinds of events are we
+            xcb::EVENT_MASK_EXPOSURE |       //   interested in
+             xcb::EVENT_MASK_KEY_PRESS | xcb::EVENT_MASK_ENTER_WINDOW |
+             xcb::EVENT_MASK_LEAVE_WINDOW |
+             xcb::EVENT_MASK_BUTTON_PRESS | xcb::EVENT_MASK_BUTTON_RELEASE |
+             xcb::EVENT_MASK_STRUCTURE_NOTIFY,
+        ),
+        (xcb::CW_OVERRIDE_REDIRECT, 0),
+    ];
+
+    // Windows with a depth different from the root window
+    // need a colormap and a border pixel of their own.
+    if let Some(colormap) = visual.colormap {
+        values.push((xcb::CW_BORDER_PIXEL, 0));
+        values.push((xcb::CW_COLORMAP, colormap));
+    }
+
     xcb::create_window(
         conn,
-        xcb::WINDOW_CLASS_COPY_FROM_PARENT as u8,
+        visual.depth,
         window,
         screen.root(),
         geometry.x(),
@@ -478,31 +1154,15 @@ fn create_window<'s>(
         geometry.height(),
         0,
         xcb::WINDOW_CLASS_INPUT_OUTPUT as u16,
-        screen.root_visual(),
-        &[
-            (xcb::CW_BACK_PIXEL, background), // Default background color
-            (
-                xcb::CW_EVENT_MASK, // What kinds of events are we
-                xcb::EVENT_MASK_EXPOSURE |       //   interested in
-             xcb::EVENT_MASK_KEY_PRESS | xcb::EVENT_MASK_ENTER_WINDOW,
-            ),
-            (xcb::CW_OVERRIDE_REDIRECT, 0),
-        ],
+        visual.visualtype.visual_id(),
+        &values,
     );
 
-    // TODO: Struts tell the WM to reserve space for our bar. Derive these from the Geometry struct.
-    let mut strut = vec![0; 12];
-    strut[2] = 30;
-    strut[8] = 5;
-    strut[9] = 1915;
-
     set_prop!(conn, window, "_NET_WM_WINDOW_TYPE", @atom "_NET_WM_WINDOW_TYPE_DOCK");
     set_prop!(conn, window, "_NET_WM_STATE", @atom "_NET_WM_STATE_STICKY");
     set_prop!(conn, window, "_NET_WM_DESKTOP", &[-1]);
-    set_prop!(conn, window, "_NET_WM_STRUT_PARTIAL", strut.as_slice());
-    set_prop!(conn, window, "_NET_WM_STRUT", &strut[0..4]);
-    set_prop!(conn, window, "_NET_WM_NAME", window_title, "UTF8_STRING", 8);
-    set_prop!(conn, window, "WM_NAME", window_title, "STRING", 8);
+    set_struts(conn, window, strut);
+    set_title(conn, window, window_title);
 
     // Request the WM to manage our window.
     xcb::map_window(conn, window);
@@ -521,3 +1181,76 @@ fn create_window<'s>(
 
     Ok(window)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use component::Component;
+    use components::Text;
+    use futures::stream::{empty, Empty};
+
+    /// A component that can't be started.
+    struct Failing;
+
+    impl Component for Failing {
+        type Error = Error;
+        type Stream = Empty<String, Error>;
+
+        fn init(&mut self) -> Result<()> {
+            Err("init failed".into())
+        }
+
+        fn stream(self, _: Handle) -> Self::Stream {
+            empty()
+        }
+    }
+
+    fn item(key: &str, creator: Box<ComponentCreator>) -> Item<'static> {
+        Item {
+            name: None,
+            slot: Slot::Left,
+            output: None,
+            style: Style::default(),
+            key: Some(key.to_string()),
+            creator,
+        }
+    }
+
+    fn text(key: &str) -> Item<'static> {
+        item(key, Box::new(Text { text: key.to_string() }))
+    }
+
+    #[test]
+    fn reuses_components_by_key() {
+        let core = Core::new().unwrap();
+        let (sender, _receiver) = mpsc::unbounded();
+        let mut running = vec![start_component(text("kept"), 0, &core.handle(), &sender).unwrap()];
+
+        let items = vec![text("new"), text("kept")];
+        let components = restart_components(items, &mut running, &core.handle(), &sender).unwrap();
+        assert!(running.is_empty());
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[1].key, Some("kept".to_string()));
+        assert_eq!(components[1].id.get(), Some(1));
+    }
+
+    #[test]
+    fn failed_reload_keeps_running_components() {
+        let core = Core::new().unwrap();
+        let (sender, _receiver) = mpsc::unbounded();
+        let mut running = vec![
+            start_component(text("kept"), 0, &core.handle(), &sender).unwrap(),
+            start_component(text("replaced"), 1, &core.handle(), &sender).unwrap(),
+        ];
+
+        let items = vec![text("kept"), item("failing", Box::new(Failing))];
+        assert!(restart_components(items, &mut running, &core.handle(), &sender).is_err());
+        assert_eq!(running.len(), 2);
+        assert_eq!(running[0].id.get(), Some(0));
+        assert!(running[0].stream.borrow().is_some());
+
+        // The component that was about to be replaced has been stopped
+        assert_eq!(running[1].id.get(), None);
+        assert!(running[1].stream.borrow().is_none());
+    }
+}
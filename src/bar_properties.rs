@@ -1,7 +1,7 @@
-use bar_builder::Geometry;
+use bar_builder::{self, Geometry, VerticalText};
 use pango::FontDescription;
 use xcb::Rectangle;
-use bar_builder::Color;
+use color::Color;
 
 #[derive(Clone)]
 pub struct BarProperties {
@@ -11,6 +11,7 @@ pub struct BarProperties {
     pub fg_color: Color,
     pub bg_color: Color,
     pub accent_color: Option<Color>,
+    pub vertical_text: VerticalText,
 }
 
 impl BarProperties {
@@ -21,4 +22,10 @@ impl BarProperties {
             None => &self.fg_color,
         }
     }
+
+    /// Whether the bar is vertical, stacking it's items from the top down.
+    #[inline]
+    pub fn is_vertical(&self) -> bool {
+        bar_builder::is_vertical(&self.area)
+    }
 }
@@ -5,24 +5,79 @@ use error::*;
 use std::result::Result as StdResult;
 use tokio_core::reactor::Handle;
 use components::Text;
+use xcb::Window;
 
 pub trait Component {
-    type Stream: Stream<Item = String, Error = Self::Error> + 'static;
+    /// The stream of updates. Items are usually `String`s of Pango markup,
+    /// but anything convertible into `ItemContent` is accepted.
+    type Stream: Stream<Error = Self::Error> + 'static;
     type Error: ::std::error::Error + Send + 'static;
 
     fn init(&mut self) -> StdResult<(), Self::Error> {
         Ok(())
     }
+
+    /// Returns a handler for mouse clicks on the component's item.
+    ///
+    /// This is called once after `init` and before `stream` consumes the
+    /// component, so the handler must own or share any state it needs.
+    fn on_click(&mut self) -> Option<ClickHandler> {
+        None
+    }
+
+    /// Returns a handler for scroll wheel events on the component's item.
+    ///
+    /// Like `on_click`, this is called once before `stream`.
+    fn on_scroll(&mut self) -> Option<ScrollHandler> {
+        None
+    }
+
+    /// Returns an `Embedder` for components that place child windows
+    /// of their own inside the item instead of only drawing markup.
+    ///
+    /// Like `on_click`, this is called once before `stream`.
+    fn embedder(&mut self) -> Option<Box<Embedder>> {
+        None
+    }
+
+    /// Returns true if the component can update it's value right away when asked to,
+    /// for example when a refresh is requested through the control socket.
+    /// The requests are passed to `refreshed_stream`.
+    fn refreshable(&self) -> bool {
+        false
+    }
+
     fn stream(self, handle: Handle) -> Self::Stream;
+
+    /// Like `stream`, with `refreshes` yielding every time the component is asked
+    /// to update it's value. Only called if `refreshable` returns true.
+    fn refreshed_stream(self, handle: Handle, _refreshes: RefreshStream) -> Self::Stream
+    where
+        Self: Sized,
+    {
+        self.stream(handle)
+    }
 }
 
 pub trait ComponentCreator {
     fn init(&mut self) -> StdResult<(), Error>;
-    fn into_stream(self: Box<Self>, handle: Handle) -> Box<Stream<Item = String, Error = Error>>;
+    fn on_click(&mut self) -> Option<ClickHandler>;
+    fn on_scroll(&mut self) -> Option<ScrollHandler>;
+    fn embedder(&mut self) -> Option<Box<Embedder>>;
+    fn refreshable(&self) -> bool;
+    fn into_stream(
+        self: Box<Self>,
+        handle: Handle,
+    ) -> Box<Stream<Item = ItemContent, Error = Error>>;
+    fn into_refreshed_stream(
+        self: Box<Self>,
+        handle: Handle,
+        refreshes: RefreshStream,
+    ) -> Box<Stream<Item = ItemContent, Error = Error>>;
     fn create(
         mut self: Box<Self>,
         handle: Handle,
-    ) -> StdResult<Box<Stream<Item = String, Error = Error>>, Error> {
+    ) -> StdResult<Box<Stream<Item = ItemContent, Error = Error>>, Error> {
         self.init()?;
         Ok(self.into_stream(handle))
     }
@@ -31,14 +86,47 @@ pub trait ComponentCreator {
 impl<C> ComponentCreator for C
 where
     C: Component,
+    <C::Stream as Stream>::Item: Into<ItemContent>,
 {
     fn init(&mut self) -> StdResult<(), Error> {
         Component::init(self).chain_err(|| "Failed to initialize component")
     }
 
-    fn into_stream(self: Box<Self>, handle: Handle) -> Box<Stream<Item = String, Error = Error>> {
+    fn on_click(&mut self) -> Option<ClickHandler> {
+        Component::on_click(self)
+    }
+
+    fn on_scroll(&mut self) -> Option<ScrollHandler> {
+        Component::on_scroll(self)
+    }
+
+    fn embedder(&mut self) -> Option<Box<Embedder>> {
+        Component::embedder(self)
+    }
+
+    fn refreshable(&self) -> bool {
+        Component::refreshable(self)
+    }
+
+    fn into_stream(
+        self: Box<Self>,
+        handle: Handle,
+    ) -> Box<Stream<Item = ItemContent, Error = Error>> {
         Box::new(
             self.stream(handle)
+                .map(Into::into)
+                .map_err(|e| Error::with_chain(e, "Component raised an error")),
+        )
+    }
+
+    fn into_refreshed_stream(
+        self: Box<Self>,
+        handle: Handle,
+        refreshes: RefreshStream,
+    ) -> Box<Stream<Item = ItemContent, Error = Error>> {
+        Box::new(
+            self.refreshed_stream(handle, refreshes)
+                .map(Into::into)
                 .map_err(|e| Error::with_chain(e, "Component raised an error")),
         )
     }
@@ -70,9 +158,151 @@ pub enum Slot {
     Center,
 }
 
+impl Slot {
+    /// The name of the slot in lowercase.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Slot::Left => "left",
+            Slot::Right => "right",
+            Slot::Center => "center",
+        }
+    }
+}
+
 pub struct ComponentUpdate {
     pub slot: Slot,
-    pub index: usize,
     pub id: usize,
-    pub value: String,
+    pub value: ItemContent,
+}
+
+/// The content of an item, as emitted by a component.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ItemContent {
+    /// Pango markup drawn with the bar's font and colors.
+    Markup(String),
+    /// Pango markup drawn with the bar's accent color, for drawing
+    /// attention to the item. (Urgent workspaces, alerts, etc.)
+    Urgent(String),
+    /// Empty space of the given width, for components
+    /// that fill their item with child windows.
+    Space(u16),
 }
+
+impl ItemContent {
+    /// Returns true if the item should be drawn with the accent color.
+    pub fn is_urgent(&self) -> bool {
+        match *self {
+            ItemContent::Urgent(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if there is nothing to display.
+    pub fn is_empty(&self) -> bool {
+        match *self {
+            ItemContent::Markup(ref markup) | ItemContent::Urgent(ref markup) => markup.is_empty(),
+            ItemContent::Space(width) => width == 0,
+        }
+    }
+
+    /// Returns the markup of the content.
+    /// `Space` has no markup and results in an empty string.
+    pub fn into_markup(self) -> String {
+        match self {
+            ItemContent::Markup(markup) | ItemContent::Urgent(markup) => markup,
+            ItemContent::Space(_) => String::new(),
+        }
+    }
+}
+
+impl From<String> for ItemContent {
+    fn from(markup: String) -> ItemContent {
+        ItemContent::Markup(markup)
+    }
+}
+
+impl<'s> From<&'s str> for ItemContent {
+    fn from(markup: &'s str) -> ItemContent {
+        ItemContent::Markup(markup.to_string())
+    }
+}
+
+/// Places child windows of a component inside the item it is displayed in.
+///
+/// If the component is displayed on several panels, only the first
+/// one of them gets the child windows.
+pub trait Embedder {
+    /// Called when the item is placed on a window, which is as thick as `thickness`
+    /// across the bar. Items on vertical windows are stacked from the top down.
+    /// This may be called again when the item is moved to another window.
+    fn attach(&mut self, window: Window, thickness: u16, vertical: bool);
+    /// Called when the window the item was attached to is about to be destroyed.
+    fn detach(&mut self);
+    /// Called when the item has moved to `pos` along the window it is attached to,
+    /// which is the x coordinate on horizontal windows and the y coordinate on vertical ones.
+    fn move_to(&mut self, pos: u16);
+}
+
+/// Whether a mouse button was pressed or released.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ButtonAction {
+    Press,
+    Release,
+}
+
+/// A mouse button event on a component's item.
+#[derive(Clone, Copy, Debug)]
+pub struct ClickEvent {
+    /// The X11 button number. (1 = left, 2 = middle, 3 = right)
+    pub button: u8,
+    pub action: ButtonAction,
+    /// Pointer position relative to the left edge of the item,
+    /// or to it's top edge on vertical bars.
+    pub x: u16,
+    /// Modifier and button mask at the time of the event.
+    pub modifiers: u16,
+}
+
+pub type ClickHandler = Box<FnMut(ClickEvent)>;
+
+/// Direction of a scroll wheel event.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScrollDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl ScrollDirection {
+    /// Maps an X11 button number to a scroll direction.
+    /// Buttons 4 through 7 are used by scroll wheels.
+    pub fn from_button(button: u8) -> Option<ScrollDirection> {
+        match button {
+            4 => Some(ScrollDirection::Up),
+            5 => Some(ScrollDirection::Down),
+            6 => Some(ScrollDirection::Left),
+            7 => Some(ScrollDirection::Right),
+            _ => None,
+        }
+    }
+}
+
+/// A scroll wheel event on a component's item.
+#[derive(Clone, Copy, Debug)]
+pub struct ScrollEvent {
+    pub direction: ScrollDirection,
+    /// Number of notches scrolled, accumulated over events
+    /// that arrived in a single burst.
+    pub delta: u32,
+    /// Pointer position relative to the left edge of the item,
+    /// or to it's top edge on vertical bars.
+    pub x: u16,
+    /// Modifier and button mask at the time of the event.
+    pub modifiers: u16,
+}
+
+pub type ScrollHandler = Box<FnMut(ScrollEvent)>;
+
+/// The refreshes requested for a component, see `Component::refreshed_stream`.
+pub type RefreshStream = Box<Stream<Item = (), Error = Error>>;
@@ -0,0 +1,254 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio_core::reactor::Handle;
+use tokio_timer::Timer;
+use futures::Stream;
+use component::{Component, ItemContent};
+use error::{Error, Result};
+
+/// Displays the charge of a battery, read from `/sys/class/power_supply`.
+///
+/// The format replaces these placeholders:
+///
+/// - `{capacity}`: the charge in percent
+/// - `{icon}`: the icon of the charge level, see `icons`
+/// - `{status}`: `Charging`, `Discharging`, `Full` or whatever else the battery reports
+/// - `{time}`: the time until the battery is empty or full as `h:mm`,
+///   or nothing if it can't be calculated
+///
+/// The item is displayed in the accent color while the battery
+/// is discharging and the charge is below `urgent_below`.
+pub struct Battery {
+    /// The name of the battery device, like `BAT0`.
+    /// The first battery found is used if this is `None`.
+    pub device: Option<String>,
+    /// The directory the power supplies are read from.
+    pub root: PathBuf,
+    pub format: String,
+    /// Icons of the charge levels, from empty to full.
+    /// The range of the charge is split evenly between them.
+    pub icons: Vec<String>,
+    /// The charge in percent below which the item is urgent.
+    pub urgent_below: u8,
+    pub refresh_rate: Duration,
+    path: Option<PathBuf>,
+}
+
+impl Default for Battery {
+    /// Create the default `Battery` component.
+    /// The default by itself does not work, `init` is required.
+    fn default() -> Battery {
+        Battery {
+            device: None,
+            root: PathBuf::from("/sys/class/power_supply"),
+            format: "{capacity}%".into(),
+            icons: vec![],
+            urgent_below: 10,
+            refresh_rate: Duration::from_secs(10),
+            path: None,
+        }
+    }
+}
+
+/// What is read from the files of a battery.
+struct BatteryState {
+    capacity: u8,
+    status: String,
+    /// Time until the battery is empty or full, if it's known.
+    time: Option<Duration>,
+}
+
+impl Battery {
+    // Read the battery and format the result
+    fn get_content(&self, path: &Path) -> Result<ItemContent> {
+        let state = read_state(path)?;
+
+        let icon = if self.icons.is_empty() {
+            ""
+        } else {
+            let level = state.capacity.min(100) as usize * self.icons.len() / 101;
+            self.icons[level].as_str()
+        };
+        let time = state.time.map_or_else(String::new, |time| {
+            let minutes = time.as_secs() / 60;
+            format!("{}:{:02}", minutes / 60, minutes % 60)
+        });
+
+        let markup = self.format
+            .replace("{capacity}", &state.capacity.to_string())
+            .replace("{icon}", icon)
+            .replace("{status}", &state.status)
+            .replace("{time}", &time);
+
+        if state.status == "Discharging" && state.capacity < self.urgent_below {
+            Ok(ItemContent::Urgent(markup))
+        } else {
+            Ok(ItemContent::Markup(markup))
+        }
+    }
+}
+
+/// Reads a file of a power supply, without the trailing newline.
+fn read_value(path: &Path, name: &str) -> Result<String> {
+    let mut value = String::new();
+    File::open(path.join(name))?.read_to_string(&mut value)?;
+    Ok(value.trim().to_string())
+}
+
+/// Reads a numeric file of a power supply. Returns `None` if the battery doesn't have it.
+fn read_number(path: &Path, name: &str) -> Option<u64> {
+    read_value(path, name).ok().and_then(|value| value.parse().ok())
+}
+
+/// Reads the amount left, the amount when full and the rate of a battery
+/// with the given names, if it has the first of them.
+fn read_amounts(path: &Path, names: [&str; 3]) -> Option<(u64, Option<u64>, Option<u64>)> {
+    let now = read_number(path, names[0])?;
+    Some((now, read_number(path, names[1]), read_number(path, names[2])))
+}
+
+/// Reads the charge and status of a battery, and calculates the remaining time
+/// from either it's energy and power or it's charge and current.
+/// The amounts of the two are never mixed, as they are in different units.
+fn read_state(path: &Path) -> Result<BatteryState> {
+    let status = read_value(path, "status")?;
+
+    let amounts = read_amounts(path, ["energy_now", "energy_full", "power_now"])
+        .or_else(|| read_amounts(path, ["charge_now", "charge_full", "current_now"]));
+    let (now, full, rate) = match amounts {
+        Some((now, full, rate)) => (Some(now), full, rate),
+        None => (None, None, None),
+    };
+
+    let capacity = match (read_number(path, "capacity"), now, full) {
+        (Some(capacity), _, _) => capacity as u8,
+        (None, Some(now), Some(full)) if full > 0 => (now * 100 / full).min(100) as u8,
+        _ => return Err("Unable to read the capacity of the battery.".into()),
+    };
+
+    // The rate is per hour, while the amounts are what is left or missing
+    let remaining = match (status.as_str(), now, full) {
+        ("Discharging", Some(now), _) => Some(now),
+        ("Charging", Some(now), Some(full)) => Some(full.saturating_sub(now)),
+        _ => None,
+    };
+    let time = match (remaining, rate) {
+        (Some(remaining), Some(rate)) if rate > 0 => {
+            Some(Duration::from_secs(remaining * 3600 / rate))
+        }
+        _ => None,
+    };
+
+    Ok(BatteryState {
+        capacity,
+        status,
+        time,
+    })
+}
+
+/// Finds the first battery among the power supplies, ordered by name.
+fn find_battery(root: &Path) -> Result<PathBuf> {
+    let mut batteries = fs::read_dir(root)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            read_value(&entry.path(), "type").map_or(false, |kind| kind == "Battery")
+        })
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    batteries.sort();
+    batteries.into_iter().next().ok_or_else(
+        || "No battery found.".into(),
+    )
+}
+
+impl Component for Battery {
+    type Error = Error;
+    type Stream = Box<Stream<Item = ItemContent, Error = Error>>;
+
+    // Find the battery.
+    //
+    // # Errors
+    //
+    // Fails if the battery doesn't exist or can't be read.
+    fn init(&mut self) -> Result<()> {
+        let path = match self.device {
+            Some(ref device) => self.root.join(device),
+            None => find_battery(&self.root)?,
+        };
+        read_state(&path)?;
+        self.path = Some(path);
+        Ok(())
+    }
+
+    fn stream(self, _: Handle) -> Self::Stream {
+        let path = self.path
+            .clone()
+            .expect("Battery must be initialized before use");
+
+        let timer = Timer::default();
+        let ticks = timer
+            .interval_at(Instant::now(), self.refresh_rate)
+            .map_err(|_| Error::from("timer error"));
+
+        // A battery that has been removed is displayed as nothing
+        Box::new(ticks.map(move |()| {
+            self.get_content(&path).unwrap_or_else(
+                |_| ItemContent::Markup(String::new()),
+            )
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use components::fixture;
+
+    #[test]
+    fn finds_first_battery() {
+        assert_eq!(find_battery(&fixture("power_supply")).unwrap(), fixture("power_supply/BAT0"));
+    }
+
+    #[test]
+    fn reads_energy_and_power() {
+        let state = read_state(&fixture("power_supply/BAT0")).unwrap();
+        assert_eq!(state.capacity, 50);
+        assert_eq!(state.status, "Discharging");
+        assert_eq!(state.time, Some(Duration::from_secs(9000)));
+    }
+
+    #[test]
+    fn reads_charge_and_current() {
+        let state = read_state(&fixture("power_supply/BAT1")).unwrap();
+        assert_eq!(state.capacity, 25);
+        assert_eq!(state.status, "Charging");
+        assert_eq!(state.time, Some(Duration::from_secs(7200)));
+    }
+
+    #[test]
+    fn does_not_mix_energy_and_current() {
+        let state = read_state(&fixture("power_supply/BAT2")).unwrap();
+        assert_eq!(state.capacity, 8);
+        assert_eq!(state.time, None);
+    }
+
+    #[test]
+    fn formats_content() {
+        let battery = Battery {
+            format: "{icon} {capacity}% {status} {time}".into(),
+            icons: vec!["a".into(), "b".into(), "c".into()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            battery.get_content(&fixture("power_supply/BAT0")).unwrap(),
+            ItemContent::Markup("b 50% Discharging 2:30".into())
+        );
+        assert_eq!(
+            battery.get_content(&fixture("power_supply/BAT2")).unwrap(),
+            ItemContent::Urgent("a 8% Discharging ".into())
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use std::time::{Duration, Instant};
 use tokio_core::reactor::Handle;
 use error::{Error, Result};
-use component::Component;
+use component::{Component, RefreshStream};
 use tokio_timer::Timer;
 use futures::Stream;
 use time;
@@ -69,12 +69,25 @@ impl Component for Clock {
     type Error = Error;
     type Stream = Box<Stream<Item = String, Error = Error>>;
 
-    fn stream(self, _: Handle) -> Self::Stream {
+    fn refreshable(&self) -> bool {
+        true
+    }
+
+    fn stream(self, handle: Handle) -> Self::Stream {
+        self.refreshed_stream(handle, Box::new(::futures::stream::empty()))
+    }
+
+    fn refreshed_stream(self, _: Handle, refreshes: RefreshStream) -> Self::Stream {
         let timer = Timer::default();
 
         let format = self.format.clone();
-        timer.interval_at(Instant::now(), self.refresh_rate).and_then(move |()| {
-            Ok(time::strftime(&format, &time::now()).unwrap())
-        }).map_err(|_| "timer error".into()).boxed()
+        let ticks = timer
+            .interval_at(Instant::now(), self.refresh_rate)
+            .map_err(|_| Error::from("timer error"));
+
+        // Refreshes show the time right away, in between the ticks
+        Box::new(ticks.select(refreshes).map(move |()| {
+            time::strftime(&format, &time::now()).unwrap()
+        }))
     }
 }
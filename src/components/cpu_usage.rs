@@ -0,0 +1,204 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio_core::reactor::Handle;
+use tokio_timer::Timer;
+use futures::Stream;
+use component::Component;
+use error::{Error, Result};
+
+/// Characters of `CpuMode::Bars`, from idle to fully used.
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// What `CpuUsage` displays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CpuMode {
+    /// The usage of every core together, like `12%`.
+    Aggregate,
+    /// The usage of every core, like `10% 14% 3% 21%`.
+    PerCore,
+    /// A bar for every core, like `▂▂▁▃`.
+    Bars,
+}
+
+/// Displays how busy the CPU is, based on `/proc/stat`.
+///
+/// The usage is the share of time spent on anything but idling
+/// since the previous refresh.
+pub struct CpuUsage {
+    pub mode: CpuMode,
+    /// Number of decimals in percentages.
+    pub precision: u8,
+    pub refresh_rate: Duration,
+    /// The directory `stat` is read from.
+    pub proc_root: PathBuf,
+}
+
+impl Default for CpuUsage {
+    fn default() -> CpuUsage {
+        CpuUsage {
+            mode: CpuMode::Aggregate,
+            precision: 0,
+            refresh_rate: Duration::from_secs(2),
+            proc_root: PathBuf::from("/proc"),
+        }
+    }
+}
+
+/// Time a CPU has spent, in clock ticks.
+#[derive(Clone, Copy)]
+struct CpuTimes {
+    idle: u64,
+    total: u64,
+}
+
+/// Reads the times of every CPU. The first entry is the sum of every core,
+/// which is followed by the cores themselves.
+fn read_times(proc_root: &Path) -> Result<Vec<CpuTimes>> {
+    let mut stat = String::new();
+    File::open(proc_root.join("stat"))?.read_to_string(&mut stat)?;
+
+    stat.lines()
+        .filter(|line| line.starts_with("cpu"))
+        .map(|line| -> Result<CpuTimes> {
+            let values = line.split_whitespace()
+                .skip(1)
+                .map(|value| value.parse::<u64>())
+                .collect::<::std::result::Result<Vec<_>, _>>()
+                .map_err(|_| Error::from("Invalid /proc/stat"))?;
+            if values.len() < 4 {
+                return Err("Invalid /proc/stat".into());
+            }
+
+            // user, nice, system, idle, iowait, irq, softirq and steal.
+            // Guest time is already part of the user time.
+            let idle = values[3] + values.get(4).cloned().unwrap_or(0);
+            let total = values.iter().take(8).sum::<u64>();
+            Ok(CpuTimes { idle, total })
+        })
+        .collect()
+}
+
+/// The share of the time between two readings that wasn't spent idling, in percent.
+fn usage(previous: &CpuTimes, current: &CpuTimes) -> f64 {
+    let total = current.total.saturating_sub(previous.total);
+    let idle = current.idle.saturating_sub(previous.idle);
+    if total == 0 {
+        0.
+    } else {
+        (total - idle.min(total)) as f64 * 100. / total as f64
+    }
+}
+
+impl CpuUsage {
+    // Format the usage since the previous reading
+    fn format(&self, previous: &[CpuTimes], current: &[CpuTimes]) -> String {
+        let usages: Vec<f64> = previous
+            .iter()
+            .zip(current)
+            .map(|(previous, current)| usage(previous, current))
+            .collect();
+        let precision = self.precision as usize;
+
+        match self.mode {
+            CpuMode::Aggregate => {
+                usages.first().map_or_else(String::new, |usage| {
+                    format!("{:.*}%", precision, usage)
+                })
+            }
+            CpuMode::PerCore => {
+                usages
+                    .iter()
+                    .skip(1)
+                    .map(|usage| format!("{:.*}%", precision, usage))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+            CpuMode::Bars => {
+                usages
+                    .iter()
+                    .skip(1)
+                    .map(|usage| {
+                        let level = (usage / 100. * BARS.len() as f64) as usize;
+                        BARS[level.min(BARS.len() - 1)]
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+impl Component for CpuUsage {
+    type Error = Error;
+    type Stream = Box<Stream<Item = String, Error = Error>>;
+
+    fn init(&mut self) -> Result<()> {
+        read_times(&self.proc_root)?;
+        Ok(())
+    }
+
+    fn stream(self, _: Handle) -> Self::Stream {
+        let mut previous = read_times(&self.proc_root).unwrap_or_default();
+
+        let timer = Timer::default();
+        let ticks = timer
+            .interval(self.refresh_rate)
+            .map_err(|_| Error::from("timer error"));
+
+        Box::new(ticks.map(move |()| {
+            let current = match read_times(&self.proc_root) {
+                Ok(current) => current,
+                Err(_) => return String::new(),
+            };
+            let text = self.format(&previous, &current);
+            previous = current;
+            text
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use components::fixture;
+
+    fn times(idle: u64, total: u64) -> CpuTimes {
+        CpuTimes { idle, total }
+    }
+
+    #[test]
+    fn reads_times() {
+        let times = read_times(&fixture("proc")).unwrap();
+        assert_eq!(times.len(), 3);
+        assert_eq!((times[0].idle, times[0].total), (1500, 2000));
+        assert_eq!((times[1].idle, times[1].total), (750, 1000));
+        assert_eq!((times[2].idle, times[2].total), (750, 1000));
+    }
+
+    #[test]
+    fn calculates_usage() {
+        assert_eq!(usage(&times(1500, 2000), &times(1800, 2400)), 25.);
+        assert_eq!(usage(&times(750, 1000), &times(750, 1200)), 100.);
+        assert_eq!(usage(&times(750, 1000), &times(750, 1000)), 0.);
+    }
+
+    #[test]
+    fn formats_usage() {
+        let previous = [times(1500, 2000), times(750, 1000), times(750, 1000)];
+        let current = [times(1800, 2500), times(750, 1200), times(1050, 1300)];
+
+        let mut cpu_usage = CpuUsage {
+            precision: 1,
+            ..Default::default()
+        };
+        assert_eq!(cpu_usage.format(&previous, &current), "40.0%");
+
+        cpu_usage.mode = CpuMode::PerCore;
+        cpu_usage.precision = 0;
+        assert_eq!(cpu_usage.format(&previous, &current), "100% 0%");
+
+        cpu_usage.mode = CpuMode::Bars;
+        assert_eq!(cpu_usage.format(&previous, &current), "█▁");
+    }
+}
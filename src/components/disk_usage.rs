@@ -0,0 +1,218 @@
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio_core::reactor::Handle;
+use tokio_timer::Timer;
+use futures::Stream;
+use libc;
+use component::Component;
+use components::network_usage::{format_bytes, Scale};
+use error::{Error, ErrorKind, Result};
+
+/// Displays how full the file systems mounted at a list of mount points are.
+///
+/// The format is repeated for every mount point and replaces these placeholders:
+///
+/// - `{mount}`: the mount point
+/// - `{free}`, `{used}` and `{total}`: amounts of space, like `120.5 GiB`
+/// - `{percent}`: the share of space used
+///
+/// Free space is the space available to users without special privileges.
+pub struct DiskUsage {
+    pub mount_points: Vec<PathBuf>,
+    pub format: String,
+    /// Placed between the mount points.
+    pub separator: String,
+    pub scale: Scale,
+    /// Number of decimals in amounts and percentages.
+    pub precision: u8,
+    /// Mount points are only displayed while the percentage of their space used
+    /// is at least this high, if it's set.
+    pub threshold: Option<f64>,
+    pub refresh_rate: Duration,
+}
+
+impl Default for DiskUsage {
+    fn default() -> DiskUsage {
+        DiskUsage {
+            mount_points: vec![PathBuf::from("/")],
+            format: "{mount} {free}".into(),
+            separator: String::from(" "),
+            scale: Scale::Binary,
+            precision: 1,
+            threshold: None,
+            refresh_rate: Duration::from_secs(60),
+        }
+    }
+}
+
+/// The space of a file system, in bytes.
+struct DiskSpace {
+    free: u64,
+    used: u64,
+    total: u64,
+}
+
+impl DiskSpace {
+    /// The share of the space used, in percent. Space reserved for privileged
+    /// users doesn't count, so full file systems are at 100% like in `df`.
+    fn percent(&self) -> f64 {
+        let usable = self.used + self.free;
+        if usable == 0 {
+            0.
+        } else {
+            self.used as f64 * 100. / usable as f64
+        }
+    }
+}
+
+/// Whether a file system is mounted at `path`, which is the case if it's on another
+/// device than it's parent directory, or if it is it's own parent like `/`.
+fn is_mount_point(path: &Path) -> io::Result<bool> {
+    let metadata = fs::metadata(path)?;
+    let parent = fs::metadata(path.join(".."))?;
+    Ok(metadata.dev() != parent.dev() || metadata.ino() == parent.ino())
+}
+
+/// Reads the space of the file system mounted at `path`.
+///
+/// # Errors
+///
+/// Fails if no file system is mounted at `path`, even if it's a directory
+/// on some other file system.
+fn read_space(path: &Path) -> Result<DiskSpace> {
+    let missing = || ErrorKind::MissingMountPoint(path.display().to_string());
+
+    match is_mount_point(path) {
+        Ok(true) => {}
+        Ok(false) => return Err(missing().into()),
+        Err(err) => return Err(Error::with_chain(err, missing())),
+    }
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|_| missing())?;
+    let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(Error::with_chain(io::Error::last_os_error(), missing()));
+    }
+
+    let block_size = stat.f_frsize as u64;
+    Ok(DiskSpace {
+        free: stat.f_bavail as u64 * block_size,
+        used: (stat.f_blocks as u64).saturating_sub(stat.f_bfree as u64) * block_size,
+        total: stat.f_blocks as u64 * block_size,
+    })
+}
+
+impl DiskUsage {
+    // Read every mount point and format the ones above the threshold.
+    // Mount points that can't be read are left out, unless none of them can be.
+    fn get_text(&self) -> Result<String> {
+        let precision = self.precision as usize;
+
+        let mut mounts = vec![];
+        let mut error = None;
+        let mut read_any = false;
+        for path in &self.mount_points {
+            let space = match read_space(path) {
+                Ok(space) => space,
+                Err(err) => {
+                    error = Some(err);
+                    continue;
+                }
+            };
+            read_any = true;
+
+            let percent = space.percent();
+            if self.threshold.map_or(false, |threshold| percent < threshold) {
+                continue;
+            }
+
+            mounts.push(
+                self.format
+                    .replace("{mount}", &path.display().to_string())
+                    .replace("{free}", &format_bytes(space.free, self.scale, self.precision))
+                    .replace("{used}", &format_bytes(space.used, self.scale, self.precision))
+                    .replace("{total}", &format_bytes(space.total, self.scale, self.precision))
+                    .replace("{percent}", &format!("{:.*}%", precision, percent)),
+            );
+        }
+
+        match error {
+            Some(err) if !read_any => Err(err),
+            _ => Ok(mounts.join(&self.separator)),
+        }
+    }
+}
+
+impl Component for DiskUsage {
+    type Error = Error;
+    type Stream = Box<Stream<Item = String, Error = Error>>;
+
+    // Make sure every mount point can be read.
+    //
+    // # Errors
+    //
+    // Fails if one of the paths isn't a mount point.
+    fn init(&mut self) -> Result<()> {
+        for path in &self.mount_points {
+            read_space(path)?;
+        }
+        Ok(())
+    }
+
+    fn stream(self, _: Handle) -> Self::Stream {
+        let timer = Timer::default();
+        let ticks = timer
+            .interval_at(Instant::now(), self.refresh_rate)
+            .map_err(|_| Error::from("timer error"));
+
+        Box::new(ticks.map(move |()| self.get_text().unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use components::fixture;
+
+    fn disk_usage(mount_points: &[&str]) -> DiskUsage {
+        DiskUsage {
+            mount_points: mount_points.iter().map(PathBuf::from).collect(),
+            format: "{mount}".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn skips_missing_mount_points() {
+        let text = disk_usage(&["/nonexistent", "/"]).get_text().unwrap();
+        assert_eq!(text, "/");
+    }
+
+    #[test]
+    fn fails_if_every_mount_point_is_missing() {
+        let err = disk_usage(&["/nonexistent"]).get_text().unwrap_err();
+        match *err.kind() {
+            ErrorKind::MissingMountPoint(ref mount) => assert_eq!(mount, "/nonexistent"),
+            _ => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn rejects_directories_that_are_not_mount_points() {
+        let path = fixture("proc");
+        let err = read_space(&path).err().unwrap();
+        match *err.kind() {
+            ErrorKind::MissingMountPoint(ref mount) => {
+                assert_eq!(*mount, path.display().to_string())
+            }
+            _ => panic!("unexpected error: {}", err),
+        }
+        assert!(read_space(Path::new("/")).is_ok());
+    }
+}
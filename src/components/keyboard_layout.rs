@@ -0,0 +1,201 @@
+use component::{ButtonAction, ClickEvent, ClickHandler, Component};
+use tokio_core::reactor::Handle;
+use error::{Error, Result};
+use xcb::{self, Connection, xkb};
+use xcb_event_stream::XcbEventStream;
+use utils::{connect_xkb, intern_atom, CORE_KEYBOARD};
+use futures::stream::{once, Stream};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Displays the active keyboard layout.
+///
+/// The names of the layouts are read from the `_XKB_RULES_NAMES` property
+/// set by `setxkbmap`, and the item is updated as soon as the layout is switched.
+/// Clicking the item switches to the next layout.
+pub struct KeyboardLayout {
+    /// Labels displayed instead of the names of the layouts, for example `"us" => "EN"`.
+    pub labels: HashMap<String, String>,
+    state: Option<XkbState>,
+}
+
+impl Default for KeyboardLayout {
+    /// Create the default `KeyboardLayout` component.
+    /// The default by itself does not work, `init` is required.
+    fn default() -> KeyboardLayout {
+        KeyboardLayout {
+            labels: HashMap::new(),
+            state: None,
+        }
+    }
+}
+
+/// The connection the XKB events are received on.
+struct XkbState {
+    conn: Rc<Connection>,
+    screen_num: i32,
+    /// The first event code of the XKB extension.
+    first_event: u8,
+    rules_names: xcb::Atom,
+}
+
+impl KeyboardLayout {
+    // Get the label of the layout that is currently active
+    fn get_label(&self) -> Result<String> {
+        let state = self.state
+            .as_ref()
+            .expect("KeyboardLayout must be initialized before use");
+
+        let names = layout_names(&state.conn, state.screen_num, state.rules_names)?;
+        let group = current_group(&state.conn)? as usize;
+
+        // Fall back to the number of the group if it has no name
+        let name = names
+            .get(group)
+            .cloned()
+            .unwrap_or_else(|| (group + 1).to_string());
+        Ok(self.labels.get(&name).cloned().unwrap_or(name))
+    }
+}
+
+/// Reads the names of the configured layouts, in the order of their groups.
+fn layout_names(conn: &Connection, screen_num: i32, rules_names: xcb::Atom) -> Result<Vec<String>> {
+    let root = {
+        let setup = conn.get_setup();
+        let screen = setup
+            .roots()
+            .nth(screen_num as usize)
+            .ok_or("Unable to acquire screen.")?;
+        screen.root()
+    };
+
+    let reply = xcb::get_property(conn, false, root, rules_names, xcb::ATOM_STRING, 0, 1024)
+        .get_reply()?;
+
+    // The rules, model, layouts, variants and options, separated by nul bytes
+    let value: &[u8] = reply.value();
+    let layouts = value.split(|&byte| byte == 0).nth(2).unwrap_or(&[]);
+
+    Ok(
+        String::from_utf8_lossy(layouts)
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect(),
+    )
+}
+
+/// Switches the core keyboard to the next layout,
+/// wrapping around to the first one after the last.
+fn next_layout(conn: &Connection) -> Result<()> {
+    let count = xkb::get_controls(conn, CORE_KEYBOARD).get_reply()?.num_groups().max(1);
+    let group = (current_group(conn)? + 1) % count;
+    xkb::latch_lock_state_checked(conn, CORE_KEYBOARD, 0, 0, true, group, 0, false, 0)
+        .request_check()?;
+    Ok(())
+}
+
+/// The group of the core keyboard, which is the index of the active layout.
+fn current_group(conn: &Connection) -> Result<u8> {
+    Ok(xkb::get_state(conn, CORE_KEYBOARD).get_reply()?.group())
+}
+
+/// Whether an event means that the active layout or the layouts themselves may have changed.
+fn is_layout_change(event: &xcb::GenericEvent, first_event: u8, rules_names: xcb::Atom) -> bool {
+    let kind = event.response_type() & !0x80;
+    if kind == xcb::PROPERTY_NOTIFY {
+        let event: &xcb::PropertyNotifyEvent = unsafe { xcb::cast_event(event) };
+        return event.atom() == rules_names;
+    }
+    if kind != first_event {
+        return false;
+    }
+
+    // Every XKB event shares the same code and is told apart by it's XKB type
+    let event: &xkb::StateNotifyEvent = unsafe { xcb::cast_event(event) };
+    match event.xkb_type() {
+        xkb::NEW_KEYBOARD_NOTIFY => true,
+        xkb::STATE_NOTIFY => event.changed() & xkb::STATE_PART_GROUP_STATE as u16 != 0,
+        _ => false,
+    }
+}
+
+impl Component for KeyboardLayout {
+    type Error = Error;
+    type Stream = Box<Stream<Item = String, Error = Error>>;
+
+    // Connect to X and select the XKB events.
+    //
+    // # Errors
+    //
+    // Fails if the XKB extension is not available.
+    fn init(&mut self) -> Result<()> {
+        let (conn, screen_num) = connect_xkb()?;
+        let rules_names = intern_atom(&conn, "_XKB_RULES_NAMES")?;
+
+        let first_event = conn.get_extension_data(&mut xkb::id())
+            .ok_or("XKB extension is not available")?
+            .first_event();
+
+        let events = (xkb::EVENT_TYPE_NEW_KEYBOARD_NOTIFY | xkb::EVENT_TYPE_STATE_NOTIFY) as u16;
+        xkb::select_events_checked(&conn, CORE_KEYBOARD, events, 0, events, 0, 0, None)
+            .request_check()?;
+
+        // The layouts are replaced by setting the property on the root window
+        {
+            let setup = conn.get_setup();
+            let screen = setup
+                .roots()
+                .nth(screen_num as usize)
+                .ok_or("Unable to acquire screen.")?;
+            xcb::change_window_attributes(
+                &conn,
+                screen.root(),
+                &[(xcb::CW_EVENT_MASK, xcb::EVENT_MASK_PROPERTY_CHANGE)],
+            );
+        }
+        conn.flush();
+
+        self.state = Some(XkbState {
+            conn: Rc::new(conn),
+            screen_num,
+            first_event,
+            rules_names,
+        });
+
+        Ok(())
+    }
+
+    fn on_click(&mut self) -> Option<ClickHandler> {
+        let conn = match self.state {
+            Some(ref state) => state.conn.clone(),
+            None => return None,
+        };
+
+        Some(Box::new(move |event: ClickEvent| if event.button == 1 &&
+            event.action == ButtonAction::Press
+        {
+            if let Err(err) = next_layout(&conn) {
+                println!("Failed to switch the keyboard layout: {}", err);
+            }
+        }))
+    }
+
+    fn stream(self, handle: Handle) -> Self::Stream {
+        let initial = self.get_label();
+        let (conn, first_event, rules_names) = {
+            let state = self.state
+                .as_ref()
+                .expect("KeyboardLayout must be initialized before use");
+            (state.conn.clone(), state.first_event, state.rules_names)
+        };
+
+        let events = XcbEventStream::new(conn, &handle)
+            .unwrap()
+            .filter(move |event| is_layout_change(event, first_event, rules_names))
+            .and_then(move |_| self.get_label());
+
+        Box::new(once(initial).chain(events))
+    }
+}
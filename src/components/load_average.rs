@@ -0,0 +1,154 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio_core::reactor::Handle;
+use tokio_timer::Timer;
+use futures::Stream;
+use component::{Component, ItemContent};
+use error::{Error, Result};
+
+/// Displays the load average of the system, based on `/proc/loadavg`.
+///
+/// The format replaces `{1}`, `{5}` and `{15}` with the load
+/// averaged over the last minute, five minutes and fifteen minutes.
+///
+/// The item is displayed in the accent color while the load
+/// of the last minute is above the threshold.
+pub struct LoadAverage {
+    pub format: String,
+    /// Number of decimals in the averages.
+    pub precision: u8,
+    /// The load above which the item is urgent.
+    /// Defaults to the number of cores if this is `None`.
+    pub threshold: Option<f64>,
+    pub refresh_rate: Duration,
+    /// The directory `loadavg` and `stat` are read from.
+    pub proc_root: PathBuf,
+}
+
+impl Default for LoadAverage {
+    fn default() -> LoadAverage {
+        LoadAverage {
+            format: "{1} {5} {15}".into(),
+            precision: 2,
+            threshold: None,
+            refresh_rate: Duration::from_secs(5),
+            proc_root: PathBuf::from("/proc"),
+        }
+    }
+}
+
+/// Reads the averages of the last minute, five minutes and fifteen minutes.
+fn read_loadavg(proc_root: &Path) -> Result<[f64; 3]> {
+    let mut loadavg = String::new();
+    File::open(proc_root.join("loadavg"))?.read_to_string(&mut loadavg)?;
+
+    let mut averages = [0.; 3];
+    let mut values = loadavg.split_whitespace();
+    for average in &mut averages {
+        *average = values
+            .next()
+            .and_then(|value| value.parse().ok())
+            .ok_or("Invalid /proc/loadavg")?;
+    }
+    Ok(averages)
+}
+
+/// Counts the cores listed in `/proc/stat`.
+fn count_cores(proc_root: &Path) -> Result<usize> {
+    let mut stat = String::new();
+    File::open(proc_root.join("stat"))?.read_to_string(&mut stat)?;
+
+    // Every core has a line like `cpu3 ...`, besides the `cpu ...` line of their sum
+    let cores = stat.lines()
+        .filter(|line| {
+            line.starts_with("cpu") && line[3..].starts_with(|c: char| c.is_ascii_digit())
+        })
+        .count();
+    Ok(cores.max(1))
+}
+
+impl LoadAverage {
+    // Read the load averages and format them
+    fn get_content(&self, threshold: f64) -> Result<ItemContent> {
+        let averages = read_loadavg(&self.proc_root)?;
+        let precision = self.precision as usize;
+
+        let markup = self.format
+            .replace("{15}", &format!("{:.*}", precision, averages[2]))
+            .replace("{5}", &format!("{:.*}", precision, averages[1]))
+            .replace("{1}", &format!("{:.*}", precision, averages[0]));
+
+        if averages[0] > threshold {
+            Ok(ItemContent::Urgent(markup))
+        } else {
+            Ok(ItemContent::Markup(markup))
+        }
+    }
+}
+
+impl Component for LoadAverage {
+    type Error = Error;
+    type Stream = Box<Stream<Item = ItemContent, Error = Error>>;
+
+    // Resolve the threshold.
+    //
+    // # Errors
+    //
+    // Fails if the load averages or the cores can't be read.
+    fn init(&mut self) -> Result<()> {
+        read_loadavg(&self.proc_root)?;
+        if self.threshold.is_none() {
+            self.threshold = Some(count_cores(&self.proc_root)? as f64);
+        }
+        Ok(())
+    }
+
+    fn stream(self, _: Handle) -> Self::Stream {
+        let threshold = self.threshold.expect("LoadAverage must be initialized before use");
+
+        let timer = Timer::default();
+        let ticks = timer
+            .interval_at(Instant::now(), self.refresh_rate)
+            .map_err(|_| Error::from("timer error"));
+
+        Box::new(ticks.map(move |()| {
+            self.get_content(threshold).unwrap_or_else(
+                |_| ItemContent::Markup(String::new()),
+            )
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use components::fixture;
+
+    #[test]
+    fn reads_loadavg() {
+        assert_eq!(read_loadavg(&fixture("proc")).unwrap(), [0.52, 1.3, 2.05]);
+    }
+
+    #[test]
+    fn counts_cores() {
+        assert_eq!(count_cores(&fixture("proc")).unwrap(), 2);
+    }
+
+    #[test]
+    fn formats_content() {
+        let load_average = LoadAverage {
+            proc_root: fixture("proc"),
+            ..Default::default()
+        };
+        assert_eq!(
+            load_average.get_content(1.).unwrap(),
+            ItemContent::Markup("0.52 1.30 2.05".into())
+        );
+        assert_eq!(
+            load_average.get_content(0.5).unwrap(),
+            ItemContent::Urgent("0.52 1.30 2.05".into())
+        );
+    }
+}
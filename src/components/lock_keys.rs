@@ -0,0 +1,173 @@
+use component::Component;
+use tokio_core::reactor::Handle;
+use error::{Error, Result};
+use xcb::{self, Connection, xkb};
+use xcb_event_stream::XcbEventStream;
+use utils::{connect_xkb, intern_atom, CORE_KEYBOARD};
+use futures::stream::{once, Stream};
+use std::rc::Rc;
+
+/// The labels of a lock key, one of which is displayed depending on whether the lock is on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LockLabels {
+    pub on: String,
+    pub off: String,
+}
+
+impl LockLabels {
+    /// Labels displaying `on` while the lock is on and nothing while it's off.
+    pub fn new<T: Into<String>>(on: T) -> LockLabels {
+        LockLabels {
+            on: on.into(),
+            off: String::new(),
+        }
+    }
+}
+
+/// Displays whether Caps Lock, Num Lock and Scroll Lock are on.
+///
+/// The state is read from the keyboard indicators of XKB and the item
+/// is updated whenever an indicator changes. Labels are pango markup,
+/// and empty labels are left out.
+pub struct LockKeys {
+    pub caps_lock: LockLabels,
+    pub num_lock: LockLabels,
+    pub scroll_lock: LockLabels,
+    /// Placed between the labels of the lock keys.
+    pub separator: String,
+    state: Option<IndicatorState>,
+}
+
+impl Default for LockKeys {
+    /// Create the default `LockKeys` component.
+    /// The default by itself does not work, `init` is required.
+    fn default() -> LockKeys {
+        LockKeys {
+            caps_lock: LockLabels::new("CAPS"),
+            num_lock: LockLabels::new("NUM"),
+            scroll_lock: LockLabels::new("SCRL"),
+            separator: String::from(" "),
+            state: None,
+        }
+    }
+}
+
+/// The connection the XKB events are received on, along with the names of the indicators.
+struct IndicatorState {
+    conn: Rc<Connection>,
+    /// The first event code of the XKB extension.
+    first_event: u8,
+    caps_lock: xcb::Atom,
+    num_lock: xcb::Atom,
+    scroll_lock: xcb::Atom,
+}
+
+impl LockKeys {
+    // Get the markup for the current state of the indicators
+    fn get_markup(&self) -> Result<String> {
+        let state = self.state
+            .as_ref()
+            .expect("LockKeys must be initialized before use");
+
+        let locks = [
+            (&self.caps_lock, state.caps_lock),
+            (&self.num_lock, state.num_lock),
+            (&self.scroll_lock, state.scroll_lock),
+        ];
+
+        let mut labels = vec![];
+        for &(labels_of_lock, indicator) in &locks {
+            let label = if is_on(&state.conn, indicator)? {
+                &labels_of_lock.on
+            } else {
+                &labels_of_lock.off
+            };
+            if !label.is_empty() {
+                labels.push(label.as_str());
+            }
+        }
+
+        Ok(labels.join(&self.separator))
+    }
+}
+
+/// Whether the named indicator of the core keyboard is lit.
+/// Indicators the keyboard doesn't have are never on.
+fn is_on(conn: &Connection, indicator: xcb::Atom) -> Result<bool> {
+    let reply = xkb::get_named_indicator(
+        conn,
+        CORE_KEYBOARD,
+        xkb::LED_CLASS_DFLT_XI_CLASS as xkb::LedClassSpec,
+        xkb::ID_DFLT_XI_ID as xkb::IdSpec,
+        indicator,
+    ).get_reply()?;
+    Ok(reply.found() && reply.on())
+}
+
+/// Whether an event means that the indicators may have changed.
+fn is_indicator_change(event: &xcb::GenericEvent, first_event: u8) -> bool {
+    if event.response_type() & !0x80 != first_event {
+        return false;
+    }
+
+    // Every XKB event shares the same code and is told apart by it's XKB type
+    let event: &xkb::IndicatorStateNotifyEvent = unsafe { xcb::cast_event(event) };
+    match event.xkb_type() {
+        xkb::INDICATOR_STATE_NOTIFY | xkb::NEW_KEYBOARD_NOTIFY => true,
+        _ => false,
+    }
+}
+
+impl Component for LockKeys {
+    type Error = Error;
+    type Stream = Box<Stream<Item = String, Error = Error>>;
+
+    // Connect to X and select the XKB events.
+    //
+    // # Errors
+    //
+    // Fails if the XKB extension is not available.
+    fn init(&mut self) -> Result<()> {
+        let (conn, _) = connect_xkb()?;
+
+        let first_event = conn.get_extension_data(&mut xkb::id())
+            .ok_or("XKB extension is not available")?
+            .first_event();
+
+        let events =
+            (xkb::EVENT_TYPE_NEW_KEYBOARD_NOTIFY | xkb::EVENT_TYPE_INDICATOR_STATE_NOTIFY) as u16;
+        xkb::select_events_checked(&conn, CORE_KEYBOARD, events, 0, events, 0, 0, None)
+            .request_check()?;
+
+        let caps_lock = intern_atom(&conn, "Caps Lock")?;
+        let num_lock = intern_atom(&conn, "Num Lock")?;
+        let scroll_lock = intern_atom(&conn, "Scroll Lock")?;
+
+        self.state = Some(IndicatorState {
+            conn: Rc::new(conn),
+            first_event,
+            caps_lock,
+            num_lock,
+            scroll_lock,
+        });
+
+        Ok(())
+    }
+
+    fn stream(self, handle: Handle) -> Self::Stream {
+        let initial = self.get_markup();
+        let (conn, first_event) = {
+            let state = self.state
+                .as_ref()
+                .expect("LockKeys must be initialized before use");
+            (state.conn.clone(), state.first_event)
+        };
+
+        let events = XcbEventStream::new(conn, &handle)
+            .unwrap()
+            .filter(move |event| is_indicator_change(event, first_event))
+            .and_then(move |_| self.get_markup());
+
+        Box::new(once(initial).chain(events))
+    }
+}
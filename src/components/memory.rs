@@ -0,0 +1,163 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio_core::reactor::Handle;
+use tokio_timer::Timer;
+use futures::Stream;
+use component::Component;
+use components::network_usage::{self, Scale};
+use error::{Error, Result};
+
+/// Displays how much memory and swap is used, based on `/proc/meminfo`.
+///
+/// The format replaces these placeholders:
+///
+/// - `{used}`, `{available}` and `{total}`: amounts of memory, like `3.2 GiB`
+/// - `{percent}`: the share of memory used
+/// - `{swap_used}`, `{swap_free}` and `{swap_total}`: amounts of swap
+/// - `{swap_percent}`: the share of swap used
+///
+/// Memory that is used for caches but can be freed counts as available.
+pub struct Memory {
+    pub format: String,
+    pub scale: Scale,
+    /// Number of decimals in amounts and percentages.
+    pub precision: u8,
+    pub refresh_rate: Duration,
+    /// The directory `meminfo` is read from.
+    pub proc_root: PathBuf,
+}
+
+impl Default for Memory {
+    fn default() -> Memory {
+        Memory {
+            format: "{used}/{total}".into(),
+            scale: Scale::Binary,
+            precision: 1,
+            refresh_rate: Duration::from_secs(5),
+            proc_root: PathBuf::from("/proc"),
+        }
+    }
+}
+
+/// The fields of `/proc/meminfo` that are displayed, in bytes.
+struct MemInfo {
+    total: u64,
+    available: u64,
+    swap_total: u64,
+    swap_free: u64,
+}
+
+/// Reads `/proc/meminfo`. Fails if any of the fields is missing,
+/// which happens on kernels that are too old to have `MemAvailable`.
+fn read_meminfo(proc_root: &Path) -> Result<MemInfo> {
+    let mut meminfo = String::new();
+    File::open(proc_root.join("meminfo"))?.read_to_string(&mut meminfo)?;
+
+    // Lines look like `MemTotal:       16318000 kB`
+    let field = |name: &str| -> Result<u64> {
+        meminfo
+            .lines()
+            .find(|line| line.split(':').next() == Some(name))
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|value| value.parse::<u64>().ok())
+            .map(|kilobytes| kilobytes * 1024)
+            .ok_or_else(|| format!("{} is missing from meminfo", name).into())
+    };
+
+    Ok(MemInfo {
+        total: field("MemTotal")?,
+        available: field("MemAvailable")?,
+        swap_total: field("SwapTotal")?,
+        swap_free: field("SwapFree")?,
+    })
+}
+
+impl Memory {
+    /// Formats an amount of bytes with the unit of it's scale.
+    fn format_bytes(&self, bytes: u64) -> String {
+        network_usage::format_bytes(bytes, self.scale, self.precision)
+    }
+
+    /// Formats the share of `used` in `total`.
+    fn format_percent(&self, used: u64, total: u64) -> String {
+        let percent = if total == 0 {
+            0.
+        } else {
+            used as f64 * 100. / total as f64
+        };
+        format!("{:.*}%", self.precision as usize, percent)
+    }
+
+    // Read the memory usage and format it
+    fn get_text(&self) -> Result<String> {
+        let info = read_meminfo(&self.proc_root)?;
+        let used = info.total.saturating_sub(info.available);
+        let swap_used = info.swap_total.saturating_sub(info.swap_free);
+
+        Ok(
+            self.format
+                .replace("{used}", &self.format_bytes(used))
+                .replace("{available}", &self.format_bytes(info.available))
+                .replace("{total}", &self.format_bytes(info.total))
+                .replace("{percent}", &self.format_percent(used, info.total))
+                .replace("{swap_used}", &self.format_bytes(swap_used))
+                .replace("{swap_free}", &self.format_bytes(info.swap_free))
+                .replace("{swap_total}", &self.format_bytes(info.swap_total))
+                .replace(
+                    "{swap_percent}",
+                    &self.format_percent(swap_used, info.swap_total),
+                ),
+        )
+    }
+}
+
+impl Component for Memory {
+    type Error = Error;
+    type Stream = Box<Stream<Item = String, Error = Error>>;
+
+    fn init(&mut self) -> Result<()> {
+        read_meminfo(&self.proc_root)?;
+        Ok(())
+    }
+
+    fn stream(self, _: Handle) -> Self::Stream {
+        let timer = Timer::default();
+        let ticks = timer
+            .interval_at(Instant::now(), self.refresh_rate)
+            .map_err(|_| Error::from("timer error"));
+
+        Box::new(ticks.map(move |()| self.get_text().unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use components::fixture;
+
+    #[test]
+    fn reads_meminfo() {
+        let info = read_meminfo(&fixture("proc")).unwrap();
+        assert_eq!(info.total, 16_384_000 * 1024);
+        assert_eq!(info.available, 8_192_000 * 1024);
+        assert_eq!(info.swap_total, 2_097_152 * 1024);
+        assert_eq!(info.swap_free, 1_536_000 * 1024);
+    }
+
+    #[test]
+    fn formats_memory() {
+        let memory = Memory {
+            format: "{used}/{total} {available} {percent} \
+                     {swap_used}/{swap_total} {swap_free} {swap_percent}"
+                .into(),
+            proc_root: fixture("proc"),
+            ..Default::default()
+        };
+        assert_eq!(
+            memory.get_text().unwrap(),
+            "7.8 GiB/15.6 GiB 7.8 GiB 50.0% 548.0 MiB/2.0 GiB 1.5 GiB 26.8%"
+        );
+    }
+}
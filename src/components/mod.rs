@@ -3,9 +3,35 @@ pub mod network_usage;
 pub mod text;
 pub mod window_title;
 pub mod clock;
+pub mod tray;
+pub mod keyboard_layout;
+pub mod lock_keys;
+pub mod battery;
+pub mod cpu_usage;
+pub mod memory;
+pub mod load_average;
+pub mod uptime;
+pub mod disk_usage;
 
 pub use self::pipe::Pipe;
 pub use self::network_usage::NetworkUsage;
 pub use self::text::Text;
 pub use self::window_title::WindowTitle;
 pub use self::clock::Clock;
+pub use self::tray::Tray;
+pub use self::keyboard_layout::KeyboardLayout;
+pub use self::lock_keys::{LockKeys, LockLabels};
+pub use self::battery::Battery;
+pub use self::cpu_usage::{CpuMode, CpuUsage};
+pub use self::memory::Memory;
+pub use self::load_average::LoadAverage;
+pub use self::uptime::Uptime;
+pub use self::disk_usage::DiskUsage;
+
+/// The path of a file or directory under `tests/fixtures`.
+#[cfg(test)]
+fn fixture(path: &str) -> ::std::path::PathBuf {
+    ::std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(path)
+}
@@ -65,13 +65,39 @@ fn get_prefix(scale: Scale, power: u8) -> &'static str {
     }
 }
 
-fn get_number_scale(number: u64, scale: Scale) -> (f64, u8) {
+/// The unit of an amount of bytes scaled by `get_number_scale`.
+pub fn get_unit(scale: Scale, power: u8) -> &'static str {
+    match (scale, power) {
+        (Scale::Decimal, 0) | (Scale::Binary, 0) => "B",
+        (Scale::Decimal, 1) => "kB",
+        (Scale::Decimal, 2) => "MB",
+        (Scale::Decimal, 3) => "GB",
+        (Scale::Decimal, 4) => "TB",
+        (Scale::Binary, 1) => "KiB",
+        (Scale::Binary, 2) => "MiB",
+        (Scale::Binary, 3) => "GiB",
+        (Scale::Binary, 4) => "TiB",
+        _ => "X",
+    }
+}
+
+/// Splits a number into a value below the base of the scale and the power of the base.
+pub fn get_number_scale(number: u64, scale: Scale) -> (f64, u8) {
     let log = (number as f64).log(scale.base() as f64);
     let wholes = log.floor();
     let over = (scale.base() as f64).powf(log - wholes);
     (over, wholes as u8)
 }
 
+/// Formats an amount of bytes with the unit of it's scale, like `3.2 GiB`.
+pub fn format_bytes(bytes: u64, scale: Scale, precision: u8) -> String {
+    if bytes == 0 {
+        return format!("0 {}", get_unit(scale, 0));
+    }
+    let (number, power) = get_number_scale(bytes, scale);
+    format!("{:.*} {}", precision as usize, number, get_unit(scale, power))
+}
+
 fn get_bytes(interface: &str, dir: Direction) -> ::std::io::Result<Option<u64>> {
     let dev = ::procinfo::net::dev::dev()?
         .into_iter()
@@ -133,3 +159,17 @@ impl Component for NetworkUsage {
             .boxed()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_bytes() {
+        assert_eq!(format_bytes(0, Scale::Binary, 1), "0 B");
+        assert_eq!(format_bytes(1536, Scale::Binary, 1), "1.5 KiB");
+        assert_eq!(format_bytes(1_500_000, Scale::Decimal, 2), "1.50 MB");
+        assert_eq!(get_prefix(Scale::Decimal, 1), "kb/s");
+        assert_eq!(get_prefix(Scale::Binary, 3), "GiB/s");
+    }
+}
@@ -1,14 +1,19 @@
-use component::Component;
+use component::{Component, RefreshStream};
 use tokio_core::reactor::Handle;
 use std::io::BufReader;
 use std::process::{Stdio, Command};
-use tokio_process::{ChildStdout, CommandExt};
-use futures::{Stream, Future};
-use tokio_timer::Timer;
+use tokio_io::io::{lines, Lines};
+use tokio_process::{Child, ChildStdout, CommandExt};
+use futures::{Async, Future, Poll, Stream};
+use tokio_timer::{Sleep, Timer};
 use std::time::Duration;
-use utils::LoopFn;
 use error::Error;
 
+/// Displays the lines printed by a command.
+///
+/// If `refresh_rate` is set, the command is run again that long after it
+/// closed it's output. A refresh kills the command if it's still running
+/// and starts it again right away.
 pub struct Pipe {
     pub command: String,
     pub args: Vec<String>,
@@ -26,14 +31,102 @@ impl Default for Pipe {
 }
 
 impl Pipe {
-    fn reader(&self, handle: &Handle) -> BufReader<ChildStdout> {
+    fn run(&self, handle: &Handle) -> Run {
         let mut cmd = Command::new(self.command.as_str());
         cmd.args(self.args.as_slice());
         cmd.stdin(Stdio::inherit()).stdout(Stdio::piped());
         let mut child = cmd.spawn_async(handle).unwrap();
         let stdout = child.stdout().take().unwrap();
-        handle.spawn(child.map(|_| ()).map_err(|_| ()));
-        BufReader::new(stdout)
+        Run {
+            lines: lines(BufReader::new(stdout)),
+            child: Some(child),
+            handle: handle.clone(),
+        }
+    }
+}
+
+/// The lines printed by one run of the command.
+/// Dropping it kills the command if it's still running.
+struct Run {
+    lines: Lines<BufReader<ChildStdout>>,
+    child: Option<Child>,
+    handle: Handle,
+}
+
+impl Stream for Run {
+    type Item = String;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<String>, Error> {
+        match self.lines.poll()? {
+            Async::Ready(None) => {
+                // Let the command exit on it's own once it closed it's output
+                if let Some(child) = self.child.take() {
+                    self.handle.spawn(child.map(|_| ()).map_err(|_| ()));
+                }
+                Ok(Async::Ready(None))
+            }
+            poll => Ok(poll),
+        }
+    }
+}
+
+/// Runs the command, again after the refresh rate and on every refresh.
+struct PipeStream {
+    pipe: Pipe,
+    handle: Handle,
+    refreshes: Option<RefreshStream>,
+    run: Option<Run>,
+    sleep: Option<Sleep>,
+}
+
+impl PipeStream {
+    fn restart(&mut self) {
+        self.sleep = None;
+        self.run = Some(self.pipe.run(&self.handle));
+    }
+}
+
+impl Stream for PipeStream {
+    type Item = String;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<String>, Error> {
+        loop {
+            match self.refreshes.as_mut().map(|refreshes| refreshes.poll()) {
+                Some(Ok(Async::Ready(Some(())))) => {
+                    self.restart();
+                    continue;
+                }
+                Some(Ok(Async::Ready(None))) => self.refreshes = None,
+                Some(Err(err)) => return Err(err),
+                Some(Ok(Async::NotReady)) | None => {}
+            }
+
+            match self.sleep.as_mut().map(|sleep| sleep.poll()) {
+                Some(Ok(Async::Ready(()))) => self.restart(),
+                Some(Ok(Async::NotReady)) => return Ok(Async::NotReady),
+                Some(Err(_)) => return Err("timer error".into()),
+                None => {}
+            }
+
+            let line = match self.run.as_mut().map(|run| run.poll()) {
+                Some(line) => line?,
+                // A command without refresh rate that exited waits for a refresh
+                None if self.refreshes.is_some() => return Ok(Async::NotReady),
+                None => return Ok(Async::Ready(None)),
+            };
+
+            match line {
+                Async::Ready(None) => {
+                    self.run = None;
+                    if let Some(refresh_rate) = self.pipe.refresh_rate {
+                        self.sleep = Some(Timer::default().sleep(refresh_rate));
+                    }
+                }
+                line => return Ok(line),
+            }
+        }
     }
 }
 
@@ -41,28 +134,29 @@ impl Component for Pipe {
     type Error = Error;
     type Stream = Box<Stream<Item = String, Error = Error>>;
 
+    fn refreshable(&self) -> bool {
+        true
+    }
+
     fn stream(self, handle: Handle) -> Self::Stream {
-        if let Some(refresh_rate) = self.refresh_rate {
-            Box::new(
-                LoopFn::new(move || {
-                    let timer = Timer::default();
-                    Ok::<_, Error>(
-                        ::tokio_io::io::lines(self.reader(&handle))
-                            .map(Some)
-                            .chain(
-                                timer
-                                    .sleep(refresh_rate)
-                                    .into_stream()
-                                    .map(|_| None)
-                                    .from_err(),
-                            )
-                            .map_err(Error::from),
-                    )
-                }).flatten()
-                    .filter_map(|s| s),
-            )
-        } else {
-            Box::new(::tokio_io::io::lines(self.reader(&handle)).from_err())
-        }
+        let run = self.run(&handle);
+        Box::new(PipeStream {
+            pipe: self,
+            handle,
+            refreshes: None,
+            run: Some(run),
+            sleep: None,
+        })
+    }
+
+    fn refreshed_stream(self, handle: Handle, refreshes: RefreshStream) -> Self::Stream {
+        let run = self.run(&handle);
+        Box::new(PipeStream {
+            pipe: self,
+            handle,
+            refreshes: Some(refreshes),
+            run: Some(run),
+            sleep: None,
+        })
     }
 }
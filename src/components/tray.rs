@@ -0,0 +1,389 @@
+use component::{Component, Embedder, ItemContent};
+use tokio_core::reactor::Handle;
+use error::{Error, Result, ErrorKind};
+use xcb::{self, Connection, Window};
+use xcb_event_stream::XcbEventStream;
+use utils::intern_atom;
+use futures::stream::{once, Stream};
+use futures::unsync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const SYSTEM_TRAY_REQUEST_DOCK: u32 = 0;
+const XEMBED_EMBEDDED_NOTIFY: u32 = 0;
+
+/// A system tray implementing the freedesktop.org system tray protocol.
+///
+/// Icons are embedded into the bar window using XEmbed. Only one tray can be
+/// running on a screen at a time, so if the bar is displayed on several outputs
+/// the tray should be added to only one of them with `BarBuilder::add_component_on`.
+pub struct Tray {
+    /// Size of the icons in pixels. Defaults to the height of the bar,
+    /// or it's width if the bar is vertical.
+    pub icon_size: Option<u16>,
+    /// Space between the icons in pixels.
+    pub spacing: u16,
+    state: Option<Rc<RefCell<TrayState>>>,
+    resizes: Option<UnboundedReceiver<()>>,
+}
+
+impl Default for Tray {
+    /// Create the default `Tray` component.
+    /// The default by itself does not work, `init` is required.
+    fn default() -> Tray {
+        Tray {
+            icon_size: None,
+            spacing: 2,
+            state: None,
+            resizes: None,
+        }
+    }
+}
+
+/// State shared between the tray's event stream and it's `Embedder`.
+struct TrayState {
+    conn: Rc<Connection>,
+    root: Window,
+    owner: Window,
+    selection: xcb::Atom,
+    opcode: xcb::Atom,
+    orientation: xcb::Atom,
+    xembed: xcb::Atom,
+    icons: Vec<Window>,
+    parent: Option<Window>,
+    /// Size of the bar across the icons.
+    thickness: u16,
+    /// Whether the icons are stacked from the top down.
+    vertical: bool,
+    /// Position of the item along the bar.
+    pos: u16,
+    icon_size: Option<u16>,
+    spacing: u16,
+    /// Notifies the stream that the width of the tray changed with the icon size.
+    resizes: UnboundedSender<()>,
+}
+
+impl TrayState {
+    fn icon_size(&self) -> u16 {
+        self.icon_size.unwrap_or(self.thickness)
+    }
+
+    /// Length of the item needed to fit every icon.
+    fn width(&self) -> u16 {
+        match self.icons.len() as u16 {
+            0 => 0,
+            n => n * self.icon_size() + (n - 1) * self.spacing,
+        }
+    }
+
+    /// Moves every icon to it's place inside the item.
+    fn layout(&self) {
+        let size = self.icon_size();
+        let across = (self.thickness.saturating_sub(size) / 2) as u32;
+
+        for (n, icon) in self.icons.iter().enumerate() {
+            let along = self.pos as u32 + n as u32 * (size + self.spacing) as u32;
+            let (x, y) = if self.vertical {
+                (across, along)
+            } else {
+                (along, across)
+            };
+            xcb::configure_window(
+                &self.conn,
+                *icon,
+                &[
+                    (xcb::CONFIG_WINDOW_X as u16, x),
+                    (xcb::CONFIG_WINDOW_Y as u16, y),
+                    (xcb::CONFIG_WINDOW_WIDTH as u16, size as u32),
+                    (xcb::CONFIG_WINDOW_HEIGHT as u16, size as u32),
+                ],
+            );
+        }
+        self.conn.flush();
+    }
+
+    /// Embeds an icon window that requested to be docked.
+    fn dock(&mut self, icon: Window) {
+        if self.icons.contains(&icon) {
+            return;
+        }
+
+        // Get notified when the icon goes away, and make sure it survives
+        // if the bar exits without giving the icon back.
+        xcb::change_window_attributes(
+            &self.conn,
+            icon,
+            &[(xcb::CW_EVENT_MASK, xcb::EVENT_MASK_STRUCTURE_NOTIFY)],
+        );
+        xcb::change_save_set(&self.conn, xcb::SET_MODE_INSERT as u8, icon);
+
+        let parent = self.parent.unwrap_or(self.root);
+        xcb::reparent_window(&self.conn, icon, parent, 0, 0);
+
+        let message = xcb::ClientMessageEvent::new(
+            32,
+            icon,
+            self.xembed,
+            xcb::ClientMessageData::from_data32(
+                [xcb::CURRENT_TIME, XEMBED_EMBEDDED_NOTIFY, 0, parent, 0],
+            ),
+        );
+        xcb::send_event(&self.conn, false, icon, xcb::EVENT_MASK_NO_EVENT, &message);
+
+        if self.parent.is_some() {
+            xcb::map_window(&self.conn, icon);
+        }
+
+        self.icons.push(icon);
+        self.layout();
+    }
+
+    /// Forgets an icon that has been destroyed or taken by someone else.
+    fn undock(&mut self, icon: Window) -> bool {
+        let count = self.icons.len();
+        self.icons.retain(|&other| other != icon);
+        if self.icons.len() == count {
+            return false;
+        }
+
+        self.layout();
+        true
+    }
+
+    /// Handles an event on the tray's connection.
+    /// Returns the new width of the tray if the icons changed.
+    fn handle_event(&mut self, event: &xcb::GenericEvent) -> Option<ItemContent> {
+        match event.response_type() & !0x80 {
+            xcb::CLIENT_MESSAGE => {
+                let event: &xcb::ClientMessageEvent = unsafe { xcb::cast_event(event) };
+                let data = event.data().data32();
+                if event.type_() == self.opcode && data[1] == SYSTEM_TRAY_REQUEST_DOCK {
+                    self.dock(data[2]);
+                    return Some(ItemContent::Space(self.width()));
+                }
+            }
+            xcb::DESTROY_NOTIFY => {
+                let event: &xcb::DestroyNotifyEvent = unsafe { xcb::cast_event(event) };
+                if self.undock(event.window()) {
+                    return Some(ItemContent::Space(self.width()));
+                }
+            }
+            xcb::REPARENT_NOTIFY => {
+                let event: &xcb::ReparentNotifyEvent = unsafe { xcb::cast_event(event) };
+                let ours = Some(event.parent()) == self.parent || event.parent() == self.root;
+                if !ours && self.undock(event.window()) {
+                    return Some(ItemContent::Space(self.width()));
+                }
+            }
+            xcb::SELECTION_CLEAR => {
+                let event: &xcb::SelectionClearEvent = unsafe { xcb::cast_event(event) };
+                if event.selection() == self.selection {
+                    // Another tray took over, give the icons back
+                    for icon in ::std::mem::replace(&mut self.icons, vec![]) {
+                        xcb::reparent_window(&self.conn, icon, self.root, 0, 0);
+                    }
+                    self.conn.flush();
+                    return Some(ItemContent::Space(0));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+impl Drop for TrayState {
+    fn drop(&mut self) {
+        for icon in &self.icons {
+            xcb::reparent_window(&self.conn, *icon, self.root, 0, 0);
+        }
+        xcb::destroy_window(&self.conn, self.owner);
+        self.conn.flush();
+    }
+}
+
+/// Places the tray icons inside the bar window.
+struct TrayEmbedder(Rc<RefCell<TrayState>>);
+
+impl Embedder for TrayEmbedder {
+    fn attach(&mut self, window: Window, thickness: u16, vertical: bool) {
+        let mut state = self.0.borrow_mut();
+        state.parent = Some(window);
+        state.vertical = vertical;
+
+        // Tells the icons which way they are laid out, 1 being vertical
+        xcb::change_property(
+            &state.conn,
+            xcb::PROP_MODE_REPLACE as u8,
+            state.owner,
+            state.orientation,
+            xcb::ATOM_CARDINAL,
+            32,
+            &[vertical as u32],
+        );
+
+        // Icons are sized after the thickness of the bar unless it's set
+        if state.thickness != thickness {
+            state.thickness = thickness;
+            if state.icon_size.is_none() && !state.icons.is_empty() {
+                let _ = state.resizes.unbounded_send(());
+            }
+        }
+
+        for icon in &state.icons {
+            xcb::reparent_window(&state.conn, *icon, window, 0, 0);
+            xcb::map_window(&state.conn, *icon);
+        }
+        state.layout();
+    }
+
+    fn detach(&mut self) {
+        let mut state = self.0.borrow_mut();
+        state.parent = None;
+
+        for icon in &state.icons {
+            xcb::unmap_window(&state.conn, *icon);
+            xcb::reparent_window(&state.conn, *icon, state.root, 0, 0);
+        }
+        state.conn.flush();
+    }
+
+    fn move_to(&mut self, pos: u16) {
+        let mut state = self.0.borrow_mut();
+        state.pos = pos;
+        state.layout();
+    }
+}
+
+impl Component for Tray {
+    type Error = Error;
+    type Stream = Box<Stream<Item = ItemContent, Error = Error>>;
+
+    /// Acquire the system tray selection of the screen.
+    ///
+    /// # Errors
+    ///
+    /// Fails if another system tray is already running.
+    fn init(&mut self) -> Result<()> {
+        let (conn, screen_num) = Connection::connect(None).map_err(ErrorKind::XcbConnection)?;
+
+        let selection = intern_atom(&conn, &format!("_NET_SYSTEM_TRAY_S{}", screen_num))?;
+        let opcode = intern_atom(&conn, "_NET_SYSTEM_TRAY_OPCODE")?;
+        let orientation = intern_atom(&conn, "_NET_SYSTEM_TRAY_ORIENTATION")?;
+        let manager = intern_atom(&conn, "MANAGER")?;
+        let xembed = intern_atom(&conn, "_XEMBED")?;
+
+        let current_owner = xcb::get_selection_owner(&conn, selection)
+            .get_reply()?
+            .owner();
+        if current_owner != xcb::NONE {
+            return Err("Another system tray is already running.".into());
+        }
+
+        let owner = conn.generate_id();
+        let root;
+
+        // Scope for `screen`
+        {
+            let setup = conn.get_setup();
+            let screen = setup
+                .roots()
+                .nth(screen_num as usize)
+                .ok_or("Unable to acquire screen.")?;
+            root = screen.root();
+
+            // The selection owner is never mapped, it only receives dock requests
+            xcb::create_window(
+                &conn,
+                xcb::WINDOW_CLASS_COPY_FROM_PARENT as u8,
+                owner,
+                root,
+                -1,
+                -1,
+                1,
+                1,
+                0,
+                xcb::WINDOW_CLASS_INPUT_OUTPUT as u16,
+                screen.root_visual(),
+                &[(xcb::CW_EVENT_MASK, xcb::EVENT_MASK_STRUCTURE_NOTIFY)],
+            );
+        }
+
+        // Icons are laid out horizontally until the tray is attached to a panel
+        xcb::change_property(
+            &conn,
+            xcb::PROP_MODE_REPLACE as u8,
+            owner,
+            orientation,
+            xcb::ATOM_CARDINAL,
+            32,
+            &[0u32],
+        );
+
+        xcb::set_selection_owner(&conn, owner, selection, xcb::CURRENT_TIME);
+        // Another tray may have taken the selection in the meantime
+        let current_owner = xcb::get_selection_owner(&conn, selection)
+            .get_reply()?
+            .owner();
+        if current_owner != owner {
+            xcb::destroy_window(&conn, owner);
+            conn.flush();
+            return Err("Another system tray is already running.".into());
+        }
+
+        // Announce the new tray to the clients
+        let message = xcb::ClientMessageEvent::new(
+            32,
+            root,
+            manager,
+            xcb::ClientMessageData::from_data32([xcb::CURRENT_TIME, selection, owner, 0, 0]),
+        );
+        xcb::send_event(&conn, false, root, xcb::EVENT_MASK_STRUCTURE_NOTIFY, &message);
+        conn.flush();
+
+        let (resizes, resizes_rx) = mpsc::unbounded();
+        self.resizes = Some(resizes_rx);
+        self.state = Some(Rc::new(RefCell::new(TrayState {
+            conn: Rc::new(conn),
+            root,
+            owner,
+            selection,
+            opcode,
+            orientation,
+            xembed,
+            icons: vec![],
+            parent: None,
+            thickness: 0,
+            vertical: false,
+            pos: 0,
+            icon_size: self.icon_size,
+            spacing: self.spacing,
+            resizes,
+        })));
+
+        Ok(())
+    }
+
+    fn embedder(&mut self) -> Option<Box<Embedder>> {
+        self.state
+            .clone()
+            .map(|state| Box::new(TrayEmbedder(state)) as Box<Embedder>)
+    }
+
+    fn stream(self, handle: Handle) -> Self::Stream {
+        let state = self.state.expect("Tray must be initialized before use");
+        let conn = state.borrow().conn.clone();
+
+        let resizes = self.resizes.expect("Tray must be initialized before use");
+
+        let resized = state.clone();
+        let resizes = resizes
+            .map(move |()| ItemContent::Space(resized.borrow().width()))
+            .map_err(|_| Error::from("channel error"));
+        let events = XcbEventStream::new(conn, &handle)
+            .unwrap()
+            .filter_map(move |event| state.borrow_mut().handle_event(&event));
+
+        Box::new(once(Ok(ItemContent::Space(0))).chain(events.select(resizes)))
+    }
+}
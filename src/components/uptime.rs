@@ -0,0 +1,97 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio_core::reactor::Handle;
+use tokio_timer::Timer;
+use futures::Stream;
+use component::Component;
+use error::{Error, Result};
+
+/// Displays how long the system has been running, based on `/proc/uptime`.
+///
+/// The format replaces `{days}` with the number of whole days, and `{hours}`
+/// and `{minutes}` with the hours and minutes on top of them, padded to two digits.
+pub struct Uptime {
+    pub format: String,
+    pub refresh_rate: Duration,
+    /// The directory `uptime` is read from.
+    pub proc_root: PathBuf,
+}
+
+impl Default for Uptime {
+    fn default() -> Uptime {
+        Uptime {
+            format: "{days}d {hours}:{minutes}".into(),
+            refresh_rate: Duration::from_secs(60),
+            proc_root: PathBuf::from("/proc"),
+        }
+    }
+}
+
+/// Reads the time since the system was booted.
+fn read_uptime(proc_root: &Path) -> Result<Duration> {
+    let mut uptime = String::new();
+    File::open(proc_root.join("uptime"))?.read_to_string(&mut uptime)?;
+
+    // The uptime is followed by the time spent idling
+    let seconds: f64 = uptime
+        .split_whitespace()
+        .next()
+        .and_then(|value| value.parse().ok())
+        .ok_or("Invalid /proc/uptime")?;
+    Ok(Duration::from_secs(seconds as u64))
+}
+
+impl Uptime {
+    // Read the uptime and format it
+    fn get_text(&self) -> Result<String> {
+        let minutes = read_uptime(&self.proc_root)?.as_secs() / 60;
+
+        Ok(
+            self.format
+                .replace("{days}", &(minutes / (24 * 60)).to_string())
+                .replace("{hours}", &format!("{:02}", minutes / 60 % 24))
+                .replace("{minutes}", &format!("{:02}", minutes % 60)),
+        )
+    }
+}
+
+impl Component for Uptime {
+    type Error = Error;
+    type Stream = Box<Stream<Item = String, Error = Error>>;
+
+    fn init(&mut self) -> Result<()> {
+        read_uptime(&self.proc_root)?;
+        Ok(())
+    }
+
+    fn stream(self, _: Handle) -> Self::Stream {
+        let timer = Timer::default();
+        let ticks = timer
+            .interval_at(Instant::now(), self.refresh_rate)
+            .map_err(|_| Error::from("timer error"));
+
+        Box::new(ticks.map(move |()| self.get_text().unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use components::fixture;
+
+    #[test]
+    fn reads_uptime() {
+        assert_eq!(read_uptime(&fixture("proc")).unwrap(), Duration::from_secs(93784));
+    }
+
+    #[test]
+    fn formats_uptime() {
+        let uptime = Uptime {
+            proc_root: fixture("proc"),
+            ..Default::default()
+        };
+        assert_eq!(uptime.get_text().unwrap(), "1d 02:03");
+    }
+}
@@ -4,6 +4,7 @@ use error::{Error, Result, ErrorKind};
 use xcb::{self, Connection};
 use xcb_event_stream;
 use futures::stream::Stream;
+use std::rc::Rc;
 
 /// This struct is used for the window title component.
 pub struct WindowTitle {
@@ -89,7 +90,7 @@ impl Component for WindowTitle {
         // Executes `get_window_title` every time window title event is received
         conn.flush();
         let active_window = self.active_window;
-        let stream = xcb_event_stream::XcbEventStream::new(conn, &handle)
+        let stream = xcb_event_stream::XcbEventStream::new(Rc::new(conn), &handle)
             .unwrap()
             .filter(move |event| unsafe {
                 let property_event: &xcb::PropertyNotifyEvent = xcb::cast_event(event);
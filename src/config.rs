@@ -0,0 +1,760 @@
+//! Declarative bar configuration, read from a TOML file.
+//!
+//! ```toml
+//! font = "Inconsolata 14"
+//! background = "#282828"
+//! foreground = "light gray"
+//! outputs = "all"
+//!
+//! [geometry]
+//! position = "top"
+//! height = 20
+//!
+//! [[left]]
+//! type = "window_title"
+//!
+//! [[right]]
+//! type = "clock"
+//! format = "%H:%M"
+//! padding_left = 4
+//! padding_right = 4
+//! ```
+
+use bar_builder::{BarBuilder, Geometry, Outputs, Position, VerticalText};
+use color::Color;
+use component::{ComponentCreator, Slot};
+use components::{Battery, Clock, CpuMode, CpuUsage, DiskUsage, KeyboardLayout, LoadAverage,
+                 LockKeys, LockLabels, Memory, NetworkUsage, Pipe, Text, Tray, Uptime,
+                 WindowTitle};
+use components::network_usage::{Direction, Scale};
+use error::{Error, Result};
+use futures::Stream;
+use futures::sync::mpsc;
+use inotify::{Inotify, WatchMask};
+use style::{Border, Style};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+use toml;
+use xcb::Rectangle;
+
+/// The whole configuration of a bar.
+///
+/// Everything is optional, anything left out uses the `BarBuilder` defaults.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub geometry: GeometryConfig,
+    pub background: Option<String>,
+    pub foreground: Option<String>,
+    pub accent: Option<String>,
+    pub font: Option<String>,
+    #[serde(default)]
+    pub inner_padding: u16,
+    pub outputs: Option<OutputsConfig>,
+    pub window_title: Option<String>,
+    pub reserve_space: Option<bool>,
+    pub vertical_text: Option<VerticalTextConfig>,
+    /// How many seconds the pointer has to be away from the bar before it collapses.
+    pub autohide: Option<f64>,
+    /// How long sliding in and out takes when the bar is shown or hidden.
+    pub slide: Option<f64>,
+    #[serde(default)]
+    pub left: Vec<ItemConfig>,
+    #[serde(default)]
+    pub center: Vec<ItemConfig>,
+    #[serde(default)]
+    pub right: Vec<ItemConfig>,
+}
+
+/// Configuration of `Geometry`.
+///
+/// Tables with `x`, `y`, `width` and `height` are absolute,
+/// anything else is relative to the output.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum GeometryConfig {
+    Absolute {
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+    },
+    Relative {
+        #[serde(default)]
+        position: PositionConfig,
+        height: u16,
+        #[serde(default)]
+        padding_x: u16,
+        #[serde(default)]
+        padding_y: u16,
+    },
+}
+
+impl Default for GeometryConfig {
+    fn default() -> GeometryConfig {
+        GeometryConfig::Relative {
+            position: PositionConfig::Top,
+            height: 20,
+            padding_x: 0,
+            padding_y: 0,
+        }
+    }
+}
+
+/// Configuration of `Position`.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PositionConfig {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Default for PositionConfig {
+    fn default() -> PositionConfig {
+        PositionConfig::Top
+    }
+}
+
+/// Configuration of `VerticalText`.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerticalTextConfig {
+    Wrapped,
+    Rotated,
+}
+
+/// Configuration of `Outputs`.
+///
+/// Either `"primary"`, `"all"`, the name of a single output
+/// or a list of output names.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum OutputsConfig {
+    Single(String),
+    Named(Vec<String>),
+}
+
+/// A component in one of the slots, along with it's style.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ItemConfig {
+    #[serde(flatten)]
+    pub component: ComponentConfig,
+    /// Refers to the component in commands.
+    pub name: Option<String>,
+    /// Only display the component on this output.
+    pub output: Option<String>,
+    #[serde(flatten)]
+    pub style: StyleConfig,
+}
+
+/// The built-in components and their parameters, selected with `type`.
+///
+/// Durations are given in seconds.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ComponentConfig {
+    Text { text: String },
+    Pipe {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        refresh_rate: Option<f64>,
+    },
+    Clock { format: Option<String> },
+    NetworkUsage {
+        interface: String,
+        #[serde(default)]
+        direction: DirectionConfig,
+        #[serde(default)]
+        decimal: bool,
+        precision: Option<u8>,
+        refresh_frequency: Option<f64>,
+        sample_duration: Option<f64>,
+    },
+    WindowTitle,
+    Tray {
+        icon_size: Option<u16>,
+        spacing: Option<u16>,
+    },
+    KeyboardLayout {
+        /// Labels displayed instead of the names of the layouts.
+        #[serde(default)]
+        labels: BTreeMap<String, String>,
+    },
+    LockKeys {
+        caps_lock: Option<LockLabelsConfig>,
+        num_lock: Option<LockLabelsConfig>,
+        scroll_lock: Option<LockLabelsConfig>,
+        separator: Option<String>,
+    },
+    Battery {
+        device: Option<String>,
+        root: Option<PathBuf>,
+        format: Option<String>,
+        #[serde(default)]
+        icons: Vec<String>,
+        urgent_below: Option<u8>,
+        refresh_rate: Option<f64>,
+    },
+    CpuUsage {
+        #[serde(default)]
+        mode: CpuModeConfig,
+        precision: Option<u8>,
+        refresh_rate: Option<f64>,
+        proc_root: Option<PathBuf>,
+    },
+    Memory {
+        format: Option<String>,
+        #[serde(default)]
+        decimal: bool,
+        precision: Option<u8>,
+        refresh_rate: Option<f64>,
+        proc_root: Option<PathBuf>,
+    },
+    LoadAverage {
+        format: Option<String>,
+        precision: Option<u8>,
+        threshold: Option<f64>,
+        refresh_rate: Option<f64>,
+        proc_root: Option<PathBuf>,
+    },
+    Uptime {
+        format: Option<String>,
+        refresh_rate: Option<f64>,
+        proc_root: Option<PathBuf>,
+    },
+    DiskUsage {
+        mount_points: Option<Vec<PathBuf>>,
+        format: Option<String>,
+        separator: Option<String>,
+        #[serde(default)]
+        decimal: bool,
+        precision: Option<u8>,
+        threshold: Option<f64>,
+        refresh_rate: Option<f64>,
+    },
+}
+
+/// Configuration of `CpuMode`.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CpuModeConfig {
+    Aggregate,
+    PerCore,
+    Bars,
+}
+
+impl Default for CpuModeConfig {
+    fn default() -> CpuModeConfig {
+        CpuModeConfig::Aggregate
+    }
+}
+
+/// Configuration of `LockLabels`. Missing labels are left empty.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockLabelsConfig {
+    #[serde(default)]
+    pub on: String,
+    #[serde(default)]
+    pub off: String,
+}
+
+/// Configuration of the network usage `Direction`.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DirectionConfig {
+    Incoming,
+    Outgoing,
+}
+
+impl Default for DirectionConfig {
+    fn default() -> DirectionConfig {
+        DirectionConfig::Incoming
+    }
+}
+
+/// Configuration of a component's `Style`.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct StyleConfig {
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+    pub font: Option<String>,
+    #[serde(default)]
+    pub padding_left: u16,
+    #[serde(default)]
+    pub padding_right: u16,
+    #[serde(default)]
+    pub margin_left: u16,
+    #[serde(default)]
+    pub margin_right: u16,
+    #[serde(default)]
+    pub border_width: u16,
+    pub border_color: Option<String>,
+}
+
+impl FromStr for Config {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Config> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+impl Config {
+    /// Reads the configuration from a TOML file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config> {
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+        contents.parse()
+    }
+
+    /// Creates a `BarBuilder` with the settings and components of the configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if a color can't be parsed or a component is misconfigured.
+    pub fn builder(&self) -> Result<BarBuilder> {
+        let mut builder = BarBuilder::new()
+            .geometry(self.geometry.to_geometry())
+            .inner_padding(self.inner_padding);
+
+        if let Some(ref color) = self.background {
+            builder = builder.background(color.parse()?);
+        }
+        if let Some(ref color) = self.foreground {
+            builder = builder.foreground(color.parse()?);
+        }
+        if let Some(ref color) = self.accent {
+            builder = builder.accent(color.parse()?);
+        }
+        if let Some(ref font) = self.font {
+            builder = builder.font(font);
+        }
+        if let Some(ref outputs) = self.outputs {
+            builder = builder.outputs(outputs.to_outputs());
+        }
+        if let Some(ref title) = self.window_title {
+            builder = builder.window_title(title.as_str());
+        }
+        if let Some(reserve_space) = self.reserve_space {
+            builder = builder.reserve_space(reserve_space);
+        }
+        if let Some(vertical_text) = self.vertical_text {
+            builder = builder.vertical_text(match vertical_text {
+                VerticalTextConfig::Wrapped => VerticalText::Wrapped,
+                VerticalTextConfig::Rotated => VerticalText::Rotated,
+            });
+        }
+        if let Some(autohide) = self.autohide {
+            builder = builder.autohide(seconds(autohide));
+        }
+        if let Some(slide) = self.slide {
+            builder = builder.slide(seconds(slide));
+        }
+
+        let slots = [
+            (Slot::Left, &self.left),
+            (Slot::Center, &self.center),
+            (Slot::Right, &self.right),
+        ];
+        for &(slot, items) in &slots {
+            for item in items {
+                builder = item.add_to(builder, slot)?;
+            }
+        }
+
+        Ok(builder)
+    }
+}
+
+/// Watches a configuration file and yields the new configuration every time it changes.
+///
+/// The directory of the file is watched instead of the file itself, so that editors
+/// replacing the file when saving it are noticed too. Configurations that fail to load
+/// are reported and skipped, so that a half written file doesn't bring the bar down.
+pub fn watch<P: AsRef<Path>>(path: P) -> Result<Box<Stream<Item = Config, Error = Error>>> {
+    let path = path.as_ref().to_path_buf();
+    let name = path.file_name()
+        .ok_or("The configuration path doesn't name a file.")?
+        .to_os_string();
+    let directory = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let mut inotify = Inotify::init()?;
+    inotify.add_watch(&directory, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)?;
+
+    // Inotify is read in a thread of it's own, since reading blocks
+    let (tx, rx) = mpsc::unbounded();
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        loop {
+            let changed = match inotify.read_events_blocking(&mut buffer) {
+                Ok(mut events) => events.any(|event| event.name == Some(name.as_os_str())),
+                Err(_) => break,
+            };
+
+            if changed && tx.unbounded_send(()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let configs = rx.map_err(|()| Error::from("channel error"))
+        .filter_map(move |()| match Config::load(&path) {
+            Ok(config) => Some(config),
+            Err(err) => {
+                println!("Failed to load the configuration: {}", err);
+                None
+            }
+        });
+
+    Ok(Box::new(configs))
+}
+
+impl GeometryConfig {
+    pub fn to_geometry(&self) -> Geometry {
+        match *self {
+            GeometryConfig::Absolute {
+                x,
+                y,
+                width,
+                height,
+            } => Geometry::Absolute(Rectangle::new(x, y, width, height)),
+            GeometryConfig::Relative {
+                position,
+                height,
+                padding_x,
+                padding_y,
+            } => Geometry::Relative {
+                position: match position {
+                    PositionConfig::Top => Position::Top,
+                    PositionConfig::Bottom => Position::Bottom,
+                    PositionConfig::Left => Position::Left,
+                    PositionConfig::Right => Position::Right,
+                },
+                height,
+                padding_x,
+                padding_y,
+            },
+        }
+    }
+}
+
+impl OutputsConfig {
+    pub fn to_outputs(&self) -> Outputs {
+        match *self {
+            OutputsConfig::Single(ref name) if name == "primary" => Outputs::Primary,
+            OutputsConfig::Single(ref name) if name == "all" => Outputs::All,
+            OutputsConfig::Single(ref name) => Outputs::Named(vec![name.clone()]),
+            OutputsConfig::Named(ref names) => Outputs::Named(names.clone()),
+        }
+    }
+}
+
+impl StyleConfig {
+    pub fn to_style(&self) -> Result<Style> {
+        let border = match self.border_width {
+            0 => None,
+            width => Some(Border {
+                width,
+                color: parse_color(&self.border_color)?.unwrap_or_else(|| Color::new(0., 0., 0.)),
+            }),
+        };
+
+        Ok(Style {
+            foreground: parse_color(&self.foreground)?,
+            background: parse_color(&self.background)?,
+            font: self.font.clone(),
+            padding_left: self.padding_left,
+            padding_right: self.padding_right,
+            margin_left: self.margin_left,
+            margin_right: self.margin_right,
+            border,
+        })
+    }
+}
+
+impl ItemConfig {
+    /// Creates the component and adds it to the builder.
+    pub fn add_to<'a>(&'a self, builder: BarBuilder<'a>, slot: Slot) -> Result<BarBuilder<'a>> {
+        match self.component {
+            ComponentConfig::Text { ref text } => {
+                add(builder, self, slot, Text { text: text.clone() })
+            }
+            ComponentConfig::Pipe {
+                ref command,
+                ref args,
+                refresh_rate,
+            } => add(
+                builder,
+                self,
+                slot,
+                Pipe {
+                    command: command.clone(),
+                    args: args.clone(),
+                    refresh_rate: refresh_rate.map(seconds),
+                },
+            ),
+            ComponentConfig::Clock { ref format } => {
+                let clock = match *format {
+                    Some(ref format) => Clock::new(format.as_str())?,
+                    None => Clock::default(),
+                };
+                add(builder, self, slot, clock)
+            }
+            ComponentConfig::NetworkUsage {
+                ref interface,
+                direction,
+                decimal,
+                precision,
+                refresh_frequency,
+                sample_duration,
+            } => {
+                let default = NetworkUsage::default();
+                let network_usage = NetworkUsage {
+                    interface: interface.clone(),
+                    direction: match direction {
+                        DirectionConfig::Incoming => Direction::Incoming,
+                        DirectionConfig::Outgoing => Direction::Outgoing,
+                    },
+                    scale: if decimal { Scale::Decimal } else { Scale::Binary },
+                    percision: precision.unwrap_or(default.percision),
+                    refresh_frequency: refresh_frequency
+                        .map_or(default.refresh_frequency, seconds),
+                    sample_duration: sample_duration.map_or(default.sample_duration, seconds),
+                };
+                add(builder, self, slot, network_usage)
+            }
+            ComponentConfig::WindowTitle => add(builder, self, slot, WindowTitle::default()),
+            ComponentConfig::Tray { icon_size, spacing } => {
+                let mut tray = Tray::default();
+                tray.icon_size = icon_size;
+                if let Some(spacing) = spacing {
+                    tray.spacing = spacing;
+                }
+                add(builder, self, slot, tray)
+            }
+            ComponentConfig::KeyboardLayout { ref labels } => {
+                let mut keyboard_layout = KeyboardLayout::default();
+                keyboard_layout.labels = labels.clone().into_iter().collect();
+                add(builder, self, slot, keyboard_layout)
+            }
+            ComponentConfig::LockKeys {
+                ref caps_lock,
+                ref num_lock,
+                ref scroll_lock,
+                ref separator,
+            } => {
+                let mut lock_keys = LockKeys::default();
+                let labels = |config: &LockLabelsConfig| {
+                    LockLabels {
+                        on: config.on.clone(),
+                        off: config.off.clone(),
+                    }
+                };
+                if let Some(ref caps_lock) = *caps_lock {
+                    lock_keys.caps_lock = labels(caps_lock);
+                }
+                if let Some(ref num_lock) = *num_lock {
+                    lock_keys.num_lock = labels(num_lock);
+                }
+                if let Some(ref scroll_lock) = *scroll_lock {
+                    lock_keys.scroll_lock = labels(scroll_lock);
+                }
+                if let Some(ref separator) = *separator {
+                    lock_keys.separator = separator.clone();
+                }
+                add(builder, self, slot, lock_keys)
+            }
+            ComponentConfig::Battery {
+                ref device,
+                ref root,
+                ref format,
+                ref icons,
+                urgent_below,
+                refresh_rate,
+            } => {
+                let mut battery = Battery::default();
+                battery.device = device.clone();
+                battery.icons = icons.clone();
+                if let Some(ref root) = *root {
+                    battery.root = root.clone();
+                }
+                if let Some(ref format) = *format {
+                    battery.format = format.clone();
+                }
+                if let Some(urgent_below) = urgent_below {
+                    battery.urgent_below = urgent_below;
+                }
+                if let Some(refresh_rate) = refresh_rate {
+                    battery.refresh_rate = seconds(refresh_rate);
+                }
+                add(builder, self, slot, battery)
+            }
+            ComponentConfig::CpuUsage {
+                mode,
+                precision,
+                refresh_rate,
+                ref proc_root,
+            } => {
+                let default = CpuUsage::default();
+                let cpu_usage = CpuUsage {
+                    mode: match mode {
+                        CpuModeConfig::Aggregate => CpuMode::Aggregate,
+                        CpuModeConfig::PerCore => CpuMode::PerCore,
+                        CpuModeConfig::Bars => CpuMode::Bars,
+                    },
+                    precision: precision.unwrap_or(default.precision),
+                    refresh_rate: refresh_rate.map_or(default.refresh_rate, seconds),
+                    proc_root: proc_root.clone().unwrap_or(default.proc_root),
+                };
+                add(builder, self, slot, cpu_usage)
+            }
+            ComponentConfig::Memory {
+                ref format,
+                decimal,
+                precision,
+                refresh_rate,
+                ref proc_root,
+            } => {
+                let default = Memory::default();
+                let memory = Memory {
+                    format: format.clone().unwrap_or(default.format),
+                    scale: if decimal { Scale::Decimal } else { Scale::Binary },
+                    precision: precision.unwrap_or(default.precision),
+                    refresh_rate: refresh_rate.map_or(default.refresh_rate, seconds),
+                    proc_root: proc_root.clone().unwrap_or(default.proc_root),
+                };
+                add(builder, self, slot, memory)
+            }
+            ComponentConfig::LoadAverage {
+                ref format,
+                precision,
+                threshold,
+                refresh_rate,
+                ref proc_root,
+            } => {
+                let default = LoadAverage::default();
+                let load_average = LoadAverage {
+                    format: format.clone().unwrap_or(default.format),
+                    precision: precision.unwrap_or(default.precision),
+                    threshold,
+                    refresh_rate: refresh_rate.map_or(default.refresh_rate, seconds),
+                    proc_root: proc_root.clone().unwrap_or(default.proc_root),
+                };
+                add(builder, self, slot, load_average)
+            }
+            ComponentConfig::Uptime {
+                ref format,
+                refresh_rate,
+                ref proc_root,
+            } => {
+                let default = Uptime::default();
+                let uptime = Uptime {
+                    format: format.clone().unwrap_or(default.format),
+                    refresh_rate: refresh_rate.map_or(default.refresh_rate, seconds),
+                    proc_root: proc_root.clone().unwrap_or(default.proc_root),
+                };
+                add(builder, self, slot, uptime)
+            }
+            ComponentConfig::DiskUsage {
+                ref mount_points,
+                ref format,
+                ref separator,
+                decimal,
+                precision,
+                threshold,
+                refresh_rate,
+            } => {
+                let default = DiskUsage::default();
+                let disk_usage = DiskUsage {
+                    mount_points: mount_points.clone().unwrap_or(default.mount_points),
+                    format: format.clone().unwrap_or(default.format),
+                    separator: separator.clone().unwrap_or(default.separator),
+                    scale: if decimal { Scale::Decimal } else { Scale::Binary },
+                    precision: precision.unwrap_or(default.precision),
+                    threshold,
+                    refresh_rate: refresh_rate.map_or(default.refresh_rate, seconds),
+                };
+                add(builder, self, slot, disk_usage)
+            }
+        }
+    }
+}
+
+/// Adds a component to the builder with the style and output of the item.
+///
+/// The parameters of the component are used as it's key,
+/// so that unchanged components keep running when the configuration is reloaded.
+fn add<'a, C>(
+    builder: BarBuilder<'a>,
+    item: &'a ItemConfig,
+    slot: Slot,
+    component: C,
+) -> Result<BarBuilder<'a>>
+where
+    C: ComponentCreator + 'static,
+{
+    let key = format!("{:?}", item.component);
+    let output = item.output.as_ref().map(String::as_str);
+    let style = item.style.to_style()?;
+    let builder = builder.add_keyed_component(Some(key), output, slot, style, component);
+    Ok(match item.name {
+        Some(ref name) => builder.name(name.as_str()),
+        None => builder,
+    })
+}
+
+fn parse_color(color: &Option<String>) -> Result<Option<Color>> {
+    match *color {
+        Some(ref color) => Ok(Some(color.parse()?)),
+        None => Ok(None),
+    }
+}
+
+fn seconds(seconds: f64) -> Duration {
+    let nanos = (seconds.fract() * 1e9) as u32;
+    Duration::new(seconds.trunc() as u64, nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_known_keys() {
+        let config: Config = "background = \"black\"\n\
+                              [geometry]\n\
+                              position = \"bottom\"\n\
+                              height = 24\n"
+            .parse()
+            .unwrap();
+        assert_eq!(config.background, Some("black".to_string()));
+        assert_eq!(
+            config.geometry,
+            GeometryConfig::Relative {
+                position: PositionConfig::Bottom,
+                height: 24,
+                padding_x: 0,
+                padding_y: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!("backgrund = \"black\"".parse::<Config>().is_err());
+        assert!("[geometry]\nheight = 24\npading_x = 4".parse::<Config>().is_err());
+    }
+}
@@ -2,6 +2,7 @@ error_chain! {
     foreign_links {
         Io(::std::io::Error);
         Timer(::tokio_timer::TimerError);
+        Toml(::toml::de::Error);
     }
 
     errors {
@@ -13,6 +14,18 @@ error_chain! {
             description("xcb error")
             display("xcb error code {}", err)
         }
+        InvalidColor(color: String) {
+            description("invalid color")
+            display("invalid color: '{}'", color)
+        }
+        DuplicateName(name: String) {
+            description("duplicate component name")
+            display("more than one component is named '{}'", name)
+        }
+        MissingMountPoint(path: String) {
+            description("missing mount point")
+            display("unable to read the file system mounted at '{}'", path)
+        }
         ItemError {
             description("item error")
             display("item error")
@@ -0,0 +1,207 @@
+//! A control socket for a running bar.
+//!
+//! Every line sent to the socket is a command, which is answered with a line of it's own.
+//! The answer is `ok` or `error: <message>`, except for `query`, which is answered
+//! with a JSON array describing every panel and the items displayed on it.
+//!
+//! ```text
+//! show
+//! hide
+//! toggle
+//! reload
+//! query
+//! refresh <component>
+//! set <component> <markup>
+//! ```
+//!
+//! Components are referred to by their name, or by their id if they don't have one.
+
+use bar::{Command, CommandStream};
+use config::Config;
+use error::{Error, Result};
+use futures::{future, Future, Stream};
+use futures::sync::oneshot;
+use futures::unsync::mpsc::{self, UnboundedSender};
+use serde_json;
+use std::env;
+use std::fs;
+use std::io::{self, BufReader};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use tokio_core::reactor::Handle;
+use tokio_io::AsyncRead;
+use tokio_io::io::{lines, write_all};
+use tokio_uds::UnixListener;
+
+/// The state of a panel, as answered to `query`.
+#[derive(Clone, Debug, Serialize)]
+pub struct PanelState {
+    pub output: String,
+    pub visible: bool,
+    pub items: Vec<ItemStateInfo>,
+}
+
+/// The state of an item on a panel, as answered to `query`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ItemStateInfo {
+    pub id: usize,
+    pub name: Option<String>,
+    pub key: Option<String>,
+    pub slot: String,
+    /// Position of the item on the panel in pixels.
+    pub x: u16,
+    pub width: u16,
+    pub urgent: bool,
+    pub markup: String,
+}
+
+/// The path of the control socket of a bar with the given name,
+/// inside `$XDG_RUNTIME_DIR` or the temporary directory.
+pub fn socket_path(name: &str) -> PathBuf {
+    let directory = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir);
+
+    // Only keep characters that are safe in a file name
+    let name: String = name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+
+    directory.join(format!("xcbars-{}.sock", name))
+}
+
+/// Removes the socket file once the listener stops.
+struct SocketFile(PathBuf);
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Listens for connections on a Unix domain socket at `path`, and returns
+/// the stream of commands received, to be passed to `BarBuilder::commands`.
+/// The socket is removed when the event loop is dropped.
+///
+/// `config` is the configuration file read again by the `reload` command.
+///
+/// # Errors
+///
+/// Fails if another bar is listening on `path` already.
+pub fn listen<P: AsRef<Path>>(
+    path: P,
+    config: Option<PathBuf>,
+    handle: &Handle,
+) -> Result<CommandStream> {
+    let path = path.as_ref();
+
+    // Only remove a socket left behind by a bar that didn't exit cleanly
+    if path.exists() {
+        match UnixStream::connect(path) {
+            Ok(_) => {
+                return Err(format!("{} is used by another bar", path.display()).into());
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+                fs::remove_file(path)?;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+    let listener = UnixListener::bind(path, handle)?;
+    let socket_file = SocketFile(path.to_path_buf());
+
+    let (tx, rx) = mpsc::unbounded();
+    let client_handle = handle.clone();
+    let server = listener.incoming().for_each(move |(stream, _)| {
+        let (reader, writer) = stream.split();
+        let tx = tx.clone();
+        let config = config.clone();
+
+        // Answer every line with a line, in the order the commands were received
+        let client = lines(BufReader::new(reader))
+            .map_err(Error::from)
+            .and_then(move |line| respond(&line, &tx, config.as_ref()))
+            .fold(writer, |writer, response| {
+                write_all(writer, response + "\n")
+                    .map(|(writer, _)| writer)
+                    .map_err(Error::from)
+            });
+
+        client_handle.spawn(client.map(|_| ()).map_err(|_| ()));
+        Ok(())
+    });
+
+    // The socket file lives as long as the server, which is dropped with the event loop
+    handle.spawn(
+        server
+            .map_err(|err| println!("Control socket failed: {}", err))
+            .then(move |result| {
+                drop(socket_file);
+                result
+            }),
+    );
+
+    Ok(Box::new(rx.map_err(|()| Error::from("channel error"))))
+}
+
+/// Passes a command on to the bar and answers it.
+fn respond(
+    line: &str,
+    tx: &UnboundedSender<Command>,
+    config: Option<&PathBuf>,
+) -> Box<Future<Item = String, Error = Error>> {
+    const STOPPED: &str = "error: the bar has stopped";
+
+    let line = line.trim();
+    if line == "query" {
+        let (reply, state) = oneshot::channel();
+        if tx.unbounded_send(Command::Query(reply)).is_err() {
+            return Box::new(future::ok(STOPPED.to_string()));
+        }
+
+        return Box::new(state.then(|state| {
+            Ok(match state {
+                Ok(state) => {
+                    serde_json::to_string(&state).unwrap_or_else(|err| format!("error: {}", err))
+                }
+                Err(_) => STOPPED.to_string(),
+            })
+        }));
+    }
+
+    let response = match parse(line, config) {
+        Ok(command) => {
+            match tx.unbounded_send(command) {
+                Ok(()) => "ok".to_string(),
+                Err(_) => STOPPED.to_string(),
+            }
+        }
+        Err(err) => format!("error: {}", err),
+    };
+    Box::new(future::ok(response))
+}
+
+/// Parses a line received on the socket into a command.
+fn parse(line: &str, config: Option<&PathBuf>) -> Result<Command> {
+    let mut words = line.splitn(2, ' ');
+    let name = words.next().unwrap_or("");
+    let arguments = words.next().unwrap_or("").trim();
+
+    match name {
+        "show" => Ok(Command::Show),
+        "hide" => Ok(Command::Hide),
+        "toggle" => Ok(Command::Toggle),
+        "reload" => {
+            let path = config.ok_or("the bar wasn't started from a configuration file")?;
+            Ok(Command::Reload(Config::load(path)?))
+        }
+        "refresh" => Ok(Command::Refresh(arguments.parse()?)),
+        "set" => {
+            let mut arguments = arguments.splitn(2, ' ');
+            let target = arguments.next().unwrap_or("").parse()?;
+            let text = arguments.next().unwrap_or("").to_string();
+            Ok(Command::SetText(target, text))
+        }
+        _ => Err(format!("unknown command '{}'", name).into()),
+    }
+}
@@ -1,20 +1,28 @@
-use pango::{self, LayoutExt, Layout};
+use pango::{self, FontDescription, LayoutExt, Layout};
 use cairo_sys;
 use pangocairo::CairoContextExt;
+use bar_builder::VerticalText;
 use bar_properties::BarProperties;
-use xcb::{self, Window, Screen, Visualtype, Pixmap, Connection};
-use cairo::{Context, Surface};
+use xcb::{self, Window, Visualtype, Pixmap, Connection};
+use cairo::{Context, Operator, Surface};
+use std::f64::consts::PI;
 use std::rc::Rc;
 use error::Result;
+use component::ItemContent;
+use style::Style;
+use color::Color;
 
 pub struct ItemState {
     bar_props: Rc<BarProperties>,
     conn: Rc<Connection>,
+    /// The size of the item along the bar, which is it's height on vertical bars.
     content_width: u16,
+    depth: u8,
+    font: FontDescription,
     id: usize,
     pixmap: Pixmap,
-    screen_number: usize,
-    state: String,
+    state: ItemContent,
+    style: Rc<Style>,
     surface: Option<Surface>,
     surface_width: u16,
     visualtype: Visualtype,
@@ -25,20 +33,27 @@ impl ItemState {
     pub fn new(
         id: usize,
         bar_props: Rc<BarProperties>,
-        screen_number: usize,
+        style: Rc<Style>,
+        depth: u8,
         visualtype: Visualtype,
         conn: Rc<Connection>,
         window: Window,
     ) -> ItemState {
         let pixmap = conn.generate_id();
+        let font = match style.font {
+            Some(ref font) => FontDescription::from_string(font),
+            None => bar_props.font.clone(),
+        };
         ItemState {
             bar_props,
             conn,
             content_width: 1,
+            depth,
+            font,
             id,
             pixmap,
-            screen_number: screen_number,
-            state: String::new(),
+            state: ItemContent::Markup(String::new()),
+            style,
             surface: None,
             surface_width: 0,
             visualtype,
@@ -67,9 +82,23 @@ impl ItemState {
     }
 
     #[inline]
-    fn get_screen(&self) -> Screen {
-        let setup = self.conn.get_setup();
-        setup.roots().nth(self.screen_number).unwrap()
+    pub fn get_state(&self) -> &ItemContent {
+        &self.state
+    }
+
+    /// The size of the bar across it's items.
+    fn thickness(&self) -> u16 {
+        if self.bar_props.is_vertical() {
+            self.bar_props.area.width()
+        } else {
+            self.bar_props.area.height()
+        }
+    }
+
+    /// Whether text is broken into lines, which happens on vertical bars
+    /// that don't rotate their text.
+    fn is_wrapped(&self) -> bool {
+        self.bar_props.is_vertical() && self.bar_props.vertical_text == VerticalText::Wrapped
     }
 
     fn update_surface(&mut self) -> Result<()> {
@@ -80,6 +109,13 @@ impl ItemState {
         {
             self.surface_width = (width / 10) * 13 + 1;
 
+            // Vertical bars have their items stacked, so the surface is turned around
+            let (surface_width, surface_height) = if self.bar_props.is_vertical() {
+                (self.thickness(), self.surface_width)
+            } else {
+                (self.surface_width, self.thickness())
+            };
+
             xcb::free_pixmap(&self.conn, self.pixmap);
             self.pixmap = self.conn.generate_id();
 
@@ -87,11 +123,11 @@ impl ItemState {
                 xcb::create_pixmap_checked,
                 "failed to create pixmap",
                 &self.conn,
-                self.get_screen().root_depth(),
+                self.depth,
                 self.pixmap,
                 self.window,
-                self.surface_width as u16,
-                self.bar_props.area.height()
+                surface_width,
+                surface_height
             );
 
             self.surface = Some(unsafe {
@@ -101,8 +137,8 @@ impl ItemState {
                     self.pixmap,
                     (&mut self.visualtype.base as *mut xcb::ffi::xcb_visualtype_t) as
                         *mut cairo_sys::xcb_visualtype_t,
-                    self.surface_width as i32,
-                    self.bar_props.area.height() as i32,
+                    surface_width as i32,
+                    surface_height as i32,
                 ))
             });
         }
@@ -112,8 +148,19 @@ impl ItemState {
     fn create_layout(&self, ctx: &Context) -> Layout {
         let layout = ctx.create_pango_layout();
 
-        layout.set_font_description(Some(&self.bar_props.font));
-        layout.set_markup(self.state.as_str(), self.state.len() as i32);
+        let markup = match self.state {
+            ItemContent::Markup(ref markup) | ItemContent::Urgent(ref markup) => markup.as_str(),
+            ItemContent::Space(_) => "",
+        };
+
+        layout.set_font_description(Some(&self.font));
+        layout.set_markup(markup, markup.len() as i32);
+        if self.is_wrapped() {
+            let width = self.thickness().saturating_sub(2 * self.style.border_width());
+            layout.set_width(width as i32 * pango::SCALE);
+            layout.set_wrap(pango::WrapMode::WordChar);
+            layout.set_alignment(pango::Alignment::Center);
+        }
         ctx.update_pango_layout(&layout);
 
         layout
@@ -129,14 +176,43 @@ impl ItemState {
         let ctx = Context::new(&surface);
         let layout = self.create_layout(&ctx);
 
-        self.content_width = layout.get_pixel_size().0 as u16;
+        let (text_width, text_height) = layout.get_pixel_size();
+        let width = match self.state {
+            ItemContent::Markup(_) | ItemContent::Urgent(_) if self.is_wrapped() => {
+                text_height as u16
+            }
+            ItemContent::Markup(_) | ItemContent::Urgent(_) => text_width as u16,
+            ItemContent::Space(width) => width,
+        };
+
+        // Empty items take no space at all, not even for their padding
+        self.content_width = if self.state.is_empty() {
+            0
+        } else {
+            width + self.style.inset_left() + self.style.inset_right()
+        };
 
         Ok(())
     }
 
-    pub fn update(&mut self, update: String) -> Result<()> {
-        if update != self.state {
-            self.state = update;
+    /// Replaces the bar properties used for painting, for example after
+    /// the bar has been resized, and repaints the item with them.
+    pub fn set_properties(&mut self, bar_props: Rc<BarProperties>) -> Result<()> {
+        self.bar_props = bar_props;
+
+        // Forces the surface to be recreated with the new height
+        self.surface = None;
+
+        if !self.state.is_empty() {
+            self.update_size()?;
+            self.paint()?;
+        }
+        Ok(())
+    }
+
+    pub fn update(&mut self, update: &ItemContent) -> Result<()> {
+        if *update != self.state {
+            self.state = update.clone();
             self.update_size()?;
             self.paint()?;
         }
@@ -154,23 +230,66 @@ impl ItemState {
         let ctx = Context::new(&surface);
         let layout = self.create_layout(&ctx);
 
-        ctx.set_source_rgb(
-            self.bar_props.bg_color.red,
-            self.bar_props.bg_color.green,
-            self.bar_props.bg_color.blue,
-        );
+        let style = &self.style;
+        let bg_color = style.background.as_ref().unwrap_or(&self.bar_props.bg_color);
+        let fg_color = if self.state.is_urgent() {
+            self.bar_props.get_accent_color()
+        } else {
+            style.foreground.as_ref().unwrap_or(&self.bar_props.fg_color)
+        };
+
+        // The margins show the background of the bar.
+        // Backgrounds replace whatever is in the pixmap, even if translucent.
+        ctx.set_operator(Operator::Source);
+        set_source_color(&ctx, &self.bar_props.bg_color);
         ctx.paint();
-        ctx.set_source_rgb(
-            self.bar_props.fg_color.red,
-            self.bar_props.fg_color.green,
-            self.bar_props.fg_color.blue,
+
+        // Everything is drawn as if the bar was horizontal. Rotated text
+        // turns the whole drawing, while wrapped text only turns the boxes.
+        let height = self.thickness() as f64;
+        let wrapped = self.is_wrapped();
+        if self.bar_props.is_vertical() && !wrapped {
+            ctx.translate(height, 0.);
+            ctx.rotate(PI / 2.);
+        }
+        let rectangle = |x: f64, y: f64, width: f64, height: f64| if wrapped {
+            ctx.rectangle(y, x, height, width);
+        } else {
+            ctx.rectangle(x, y, width, height);
+        };
+
+        let box_x = style.margin_left as f64;
+        let box_width = self.content_width
+            .saturating_sub(style.margin_left + style.margin_right) as f64;
+
+        if let Some(ref border) = style.border {
+            set_source_color(&ctx, &border.color);
+            rectangle(box_x, 0., box_width, height);
+            ctx.fill();
+        }
+
+        let border_width = style.border_width() as f64;
+        set_source_color(&ctx, bg_color);
+        rectangle(
+            box_x + border_width,
+            border_width,
+            box_width - 2. * border_width,
+            height - 2. * border_width,
         );
+        ctx.fill();
 
-        let text_height = self.bar_props.font.get_size() as f64 / pango::SCALE as f64;
-        let baseline = self.bar_props.area.height() as f64 / 2. + (text_height / 2.) -
-            (layout.get_baseline() as f64 / pango::SCALE as f64);
+        ctx.set_operator(Operator::Over);
+        set_source_color(&ctx, fg_color);
 
-        ctx.move_to(0., baseline.floor() - 1.);
+        if wrapped {
+            // The lines are centered by the layout
+            ctx.move_to(border_width, style.inset_left() as f64);
+        } else {
+            let text_height = self.font.get_size() as f64 / pango::SCALE as f64;
+            let baseline = height / 2. + (text_height / 2.) -
+                (layout.get_baseline() as f64 / pango::SCALE as f64);
+            ctx.move_to(style.inset_left() as f64, baseline.floor() - 1.);
+        }
         ctx.update_pango_layout(&layout);
         ctx.show_pango_layout(&layout);
 
@@ -179,3 +298,15 @@ impl ItemState {
         Ok(())
     }
 }
+
+fn set_source_color(ctx: &Context, color: &Color) {
+    ctx.set_source_rgba(color.red, color.green, color.blue, color.alpha);
+}
+
+impl Drop for ItemState {
+    fn drop(&mut self) {
+        if self.surface.take().is_some() {
+            xcb::free_pixmap(&self.conn, self.pixmap);
+        }
+    }
+}
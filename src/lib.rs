@@ -12,21 +12,36 @@ extern crate error_chain;
 extern crate pangocairo;
 extern crate procinfo;
 extern crate tokio_timer;
+extern crate tokio_uds;
 extern crate time;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate toml;
+extern crate inotify;
+extern crate libc;
 
 #[macro_use]
 mod utils;
 pub mod components;
+pub mod config;
+pub mod ipc;
 mod error;
 mod bar;
+mod color;
+mod panel;
+mod style;
 mod item_state;
 mod bar_builder;
 mod bar_properties;
 mod xcb_event_stream;
 pub mod component;
 
-pub use bar_builder::{Color, BarBuilder, Geometry, Position};
+pub use bar_builder::{BarBuilder, Geometry, Outputs, Position, VerticalText};
+pub use color::Color;
 pub use bar_properties::BarProperties;
 pub use component::{Slot, Component};
-pub use bar::Bar;
-pub use error::Error;
+pub use bar::{Bar, Command, Controller, Target};
+pub use style::{Border, Style};
+pub use error::{Error, ErrorKind};
@@ -0,0 +1,58 @@
+extern crate futures;
+extern crate tokio_core;
+extern crate xcbars;
+
+use futures::Stream;
+use tokio_core::reactor::Core;
+use xcbars::Command;
+use xcbars::config::{self, Config};
+use xcbars::ipc;
+use std::env;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process;
+
+/// Finds the configuration file, which is either given as the only argument
+/// or `$XDG_CONFIG_HOME/xcbars/config.toml`.
+fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::args_os().nth(1) {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("xcbars").join("config.toml"))
+}
+
+fn run() -> Result<(), xcbars::Error> {
+    let path = config_path().ok_or("Unable to locate the configuration file.")?;
+    let config = Config::load(&path)?;
+
+    let mut core = Core::new()?;
+    let handle = core.handle();
+
+    // The bar is reloaded whenever the configuration file changes,
+    // and can be controlled through a socket named after the window title.
+    let reloads = config::watch(&path)?.map(Command::Reload);
+    let title = config.window_title.as_ref().map_or("xcbars", String::as_str);
+    let socket = ipc::listen(ipc::socket_path(title), Some(path.clone()), &handle)?;
+
+    let bar = config
+        .builder()?
+        .commands(reloads.select(socket))
+        .build(handle)?;
+    core.run(bar.run()).map_err(|()| "event loop error".into())
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error occurred: {}", err);
+        let mut cause = err.cause();
+        while let Some(err) = cause {
+            eprintln!("  Caused by: {}", err);
+            cause = err.cause();
+        }
+        process::exit(1);
+    }
+}
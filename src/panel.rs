@@ -0,0 +1,737 @@
+use item_state::ItemState;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+use futures::{Future, Stream};
+use tokio_timer::Timer;
+use xcb::{self, Connection, Rectangle, Screen, Window};
+use error::*;
+use component::{Slot, ComponentUpdate};
+use bar_properties::BarProperties;
+use bar_builder::{self, BarVisual, Geometry, Position};
+use style::Style;
+
+/// How a component is displayed on the panels.
+pub struct ItemTemplate {
+    pub name: Option<String>,
+    pub slot: Slot,
+    /// The output the item is restricted to.
+    pub output: Option<String>,
+    pub style: Rc<Style>,
+}
+
+/// Everything needed to create panels and place them on outputs,
+/// both when the bar is built and when outputs change later on.
+pub struct PanelTemplate {
+    pub foreground: u32,
+    pub geometry: Geometry,
+    pub inner_padding: u16,
+    pub items: Vec<ItemTemplate>,
+    pub properties: BarProperties,
+    pub reserve_space: bool,
+    /// How long the pointer has to be away from a panel before it collapses, if it does.
+    pub autohide: Option<Duration>,
+    /// How long showing or hiding the panels takes, if they slide in and out.
+    pub slide: Option<Duration>,
+    pub visual: BarVisual,
+    pub window_title: String,
+}
+
+impl PanelTemplate {
+    /// Creates a window on an output and a panel displaying it.
+    pub fn create_panel(
+        &self,
+        conn: &Rc<Connection>,
+        window_conn: &Connection,
+        output: String,
+        output_area: Rectangle,
+    ) -> Result<Panel> {
+        let setup = conn.get_setup();
+        let screen = setup.roots().next().unwrap();
+
+        let geometry = bar_builder::calculate_geometry(&output_area, &self.geometry);
+        let strut = self.struts(&screen, &geometry, &output_area);
+
+        let window = bar_builder::create_window(
+            window_conn,
+            &screen,
+            &geometry,
+            &strut,
+            self.properties.bg_color.as_u32(),
+            &self.visual,
+            self.window_title.as_bytes(),
+        )?;
+
+        let mut panel = Panel::new(
+            conn.clone(),
+            output,
+            output_area,
+            window,
+            geometry,
+            self.foreground,
+            self.inner_padding,
+            self.items.len(),
+        );
+        self.add_items(&mut panel);
+
+        Ok(panel)
+    }
+
+    /// Replaces the items of a panel with new ones for every item of the template
+    /// displayed on the panel's output. The items start out empty.
+    pub fn add_items(&self, panel: &mut Panel) {
+        panel.clear_items(self.items.len());
+        panel.foreground = self.foreground;
+        panel.inner_padding = self.inner_padding;
+
+        let properties = self.panel_properties(panel.geometry);
+        for (id, template) in self.items.iter().enumerate() {
+            let displayed = match template.output {
+                Some(ref item_output) => *item_output == panel.output,
+                None => true,
+            };
+
+            if displayed {
+                let item = ItemState::new(
+                    id,
+                    properties.clone(),
+                    template.style.clone(),
+                    self.visual.depth,
+                    self.visual.visualtype,
+                    panel.conn.clone(),
+                    panel.window,
+                );
+                panel.add_item(template.slot, item);
+            }
+        }
+    }
+
+    /// Moves and resizes a panel to fit the current area of it's output.
+    pub fn place_panel(&self, panel: &mut Panel, output_area: Rectangle) -> Result<()> {
+        let geometry = bar_builder::calculate_geometry(&output_area, &self.geometry);
+        panel.output_area = output_area;
+
+        if !rectangles_equal(&geometry, &panel.geometry) {
+            panel.resize(geometry, self.panel_properties(geometry))?;
+        }
+
+        // Resizing moves the window back into view
+        if panel.collapsed && self.autohide.is_none() {
+            panel.collapsed = false;
+        }
+        panel.set_window_area(&self.shown_area(panel));
+
+        self.update_struts(panel);
+        Ok(())
+    }
+
+    /// Sets the struts of a panel for it's current geometry.
+    /// Hidden and collapsed panels don't reserve any space.
+    pub fn update_struts(&self, panel: &Panel) {
+        let strut = if panel.visible && !panel.collapsed {
+            let setup = panel.conn.get_setup();
+            let screen = setup.roots().next().unwrap();
+            self.struts(&screen, &panel.geometry, &panel.output_area)
+        } else {
+            [0; 12]
+        };
+        bar_builder::set_struts(&panel.conn, panel.window, &strut);
+    }
+
+    /// The area of the window of a panel that only reveals `revealed` pixels
+    /// of it's thickness, at the edge the panel is docked to.
+    ///
+    /// The window shrinks instead of moving past the edge,
+    /// so it never shows up on a neighbouring output.
+    fn revealed_area(&self, panel: &Panel, revealed: u16) -> Rectangle {
+        let g = &panel.geometry;
+        // X doesn't allow windows without any size, so one pixel is left
+        let revealed = revealed.max(1).min(panel.thickness());
+        let hidden = (panel.thickness() - revealed) as i16;
+        match bar_builder::docked_position(&self.geometry, g, &panel.output_area) {
+            Position::Top => Rectangle::new(g.x(), g.y(), g.width(), revealed),
+            Position::Bottom => Rectangle::new(g.x(), g.y() + hidden, g.width(), revealed),
+            Position::Left => Rectangle::new(g.x(), g.y(), revealed, g.height()),
+            Position::Right => Rectangle::new(g.x() + hidden, g.y(), revealed, g.height()),
+        }
+    }
+
+    /// The area of the window of a panel that has slid out of sight.
+    /// The window is unmapped once it's hidden.
+    pub fn hidden_area(&self, panel: &Panel) -> Rectangle {
+        self.revealed_area(panel, 0)
+    }
+
+    /// The area of the window of a panel while it's shown. A collapsed panel
+    /// only keeps a strip of `TRIGGER_SIZE` pixels at the edge of it's output.
+    pub fn shown_area(&self, panel: &Panel) -> Rectangle {
+        if panel.collapsed {
+            self.revealed_area(panel, TRIGGER_SIZE)
+        } else {
+            panel.geometry
+        }
+    }
+
+    /// Calculates the struts of a panel, unless reserving space is disabled.
+    fn struts<'s>(
+        &self,
+        screen: &Screen<'s>,
+        geometry: &Rectangle,
+        output_area: &Rectangle,
+    ) -> [u32; 12] {
+        if self.reserve_space {
+            bar_builder::calculate_struts(screen, &self.geometry, geometry, output_area)
+        } else {
+            [0; 12]
+        }
+    }
+
+    /// Properties of a single panel. Every panel has properties
+    /// of it's own since the area differs.
+    fn panel_properties(&self, area: Rectangle) -> Rc<BarProperties> {
+        Rc::new(BarProperties {
+            area,
+            ..self.properties.clone()
+        })
+    }
+}
+
+fn rectangles_equal(a: &Rectangle, b: &Rectangle) -> bool {
+    a.x() == b.x() && a.y() == b.y() && a.width() == b.width() && a.height() == b.height()
+}
+
+/// A single bar window displayed on one output.
+///
+/// Every panel of a bar renders the updates of the same components,
+/// but keeps its own window, item states and layout.
+pub struct Panel {
+    /// Incremented whenever the panel starts sliding, which stops the previous slide.
+    pub animation: Rc<Cell<usize>>,
+    pub center_items: Vec<ItemState>,
+    /// Whether the panel has been collapsed to it's trigger strip.
+    pub collapsed: bool,
+    pub conn: Rc<Connection>,
+    pub damage: Vec<Rectangle>,
+    pub foreground: u32,
+    pub geometry: Rectangle,
+    /// Incremented whenever the pointer enters or leaves the panel,
+    /// which cancels the pending collapse.
+    pub idle: usize,
+    pub inner_padding: u16,
+    pub item_positions: Vec<(u16, u16)>,
+    pub left_items: Vec<ItemState>,
+    pub output: String,
+    /// The area of the output the panel is displayed on.
+    pub output_area: Rectangle,
+    pub right_items: Vec<ItemState>,
+    pub slots: Vec<Option<(Slot, usize)>>,
+    pub visible: bool,
+    pub window: Window,
+}
+
+impl Panel {
+    /// Creates a panel without any items for a window that has already been created.
+    /// `item_count` is the number of components in the whole bar.
+    pub fn new(
+        conn: Rc<Connection>,
+        output: String,
+        output_area: Rectangle,
+        window: Window,
+        geometry: Rectangle,
+        foreground: u32,
+        inner_padding: u16,
+        item_count: usize,
+    ) -> Panel {
+        Panel {
+            animation: Rc::new(Cell::new(0)),
+            center_items: vec![],
+            collapsed: false,
+            conn,
+            damage: vec![],
+            foreground,
+            geometry,
+            idle: 0,
+            inner_padding,
+            item_positions: vec![(0, 0); item_count],
+            left_items: vec![],
+            output,
+            output_area,
+            right_items: vec![],
+            slots: vec![None; item_count],
+            visible: true,
+            window,
+        }
+    }
+
+    /// Removes every item of the panel.
+    /// `item_count` is the number of components in the whole bar.
+    pub fn clear_items(&mut self, item_count: usize) {
+        self.left_items.clear();
+        self.center_items.clear();
+        self.right_items.clear();
+        self.item_positions = vec![(0, 0); item_count];
+        self.slots = vec![None; item_count];
+    }
+
+    /// Adds an item to the end of a slot.
+    pub fn add_item(&mut self, slot: Slot, item: ItemState) {
+        let id = item.get_id();
+        let vec = match slot {
+            Slot::Left => &mut self.left_items,
+            Slot::Center => &mut self.center_items,
+            Slot::Right => &mut self.right_items,
+        };
+        self.slots[id] = Some((slot, vec.len()));
+        vec.push(item);
+    }
+
+    /// Applies a component update to this panel's item and redraws it.
+    /// Updates of components that aren't displayed on this panel are ignored.
+    pub fn update(&mut self, update: &ComponentUpdate) -> Result<()> {
+        let (slot, index) = match self.slots[update.id] {
+            Some(position) => position,
+            None => return Ok(()),
+        };
+
+        let size_changed;
+
+        // Figure out if the component has chanaged
+        // it's size since the last update.
+        {
+            let slot_items = match slot {
+                Slot::Left => &mut self.left_items,
+                Slot::Center => &mut self.center_items,
+                Slot::Right => &mut self.right_items,
+            };
+            slot_items[index].update(&update.value)?;
+
+            let width = slot_items[index].get_content_width();
+            size_changed = self.item_positions[update.id].1 != width;
+        }
+
+        // Redraw only neccessary stuff
+        match slot {
+            Slot::Center => self.redraw_center(),
+            Slot::Left => self.redraw_left(size_changed, index),
+            Slot::Right => self.redraw_right(size_changed, index),
+        }
+    }
+
+    /// Finds the item covering the point `(x, y)` on the panel.
+    /// Returns the id of the item and the offset of the point within it,
+    /// along the length of the panel.
+    pub fn item_at(&self, x: u16, y: u16) -> Option<(usize, u16)> {
+        let pos = if self.is_vertical() { y } else { x };
+        self.item_positions
+            .iter()
+            .enumerate()
+            .find(|&(_, &(start, width))| pos >= start && pos < start + width)
+            .map(|(id, &(start, _))| (id, pos - start))
+    }
+
+    /// Whether the panel is vertical, stacking it's items from the top down.
+    pub fn is_vertical(&self) -> bool {
+        bar_builder::is_vertical(&self.geometry)
+    }
+
+    /// The size of the panel along the direction it's items are laid out in.
+    fn length(&self) -> u16 {
+        if self.is_vertical() {
+            self.geometry.height()
+        } else {
+            self.geometry.width()
+        }
+    }
+
+    /// The size of the panel across it's items, which is the height of
+    /// a horizontal panel and the width of a vertical one.
+    pub fn thickness(&self) -> u16 {
+        if self.is_vertical() {
+            self.geometry.width()
+        } else {
+            self.geometry.height()
+        }
+    }
+
+    /// The area of the panel covering it's whole thickness from `start` for `length` pixels.
+    fn span(&self, start: u16, length: u16) -> Rectangle {
+        if self.is_vertical() {
+            Rectangle::new(0, start as i16, self.thickness(), length)
+        } else {
+            Rectangle::new(start as i16, 0, length, self.thickness())
+        }
+    }
+
+    /// Moves and resizes the panel's window.
+    /// Items are repainted with the new properties and laid out again.
+    pub fn resize(&mut self, geometry: Rectangle, properties: Rc<BarProperties>) -> Result<()> {
+        xcb::configure_window(
+            &self.conn,
+            self.window,
+            &[
+                (xcb::CONFIG_WINDOW_X as u16, geometry.x() as u32),
+                (xcb::CONFIG_WINDOW_Y as u16, geometry.y() as u32),
+                (xcb::CONFIG_WINDOW_WIDTH as u16, geometry.width() as u32),
+                (xcb::CONFIG_WINDOW_HEIGHT as u16, geometry.height() as u32),
+            ],
+        );
+        self.geometry = geometry;
+
+        for item in self.left_items
+            .iter_mut()
+            .chain(self.center_items.iter_mut())
+            .chain(self.right_items.iter_mut())
+        {
+            item.set_properties(properties.clone())?;
+        }
+
+        self.relayout()
+    }
+
+    /// Lays out and redraws every item of the panel.
+    pub fn relayout(&mut self) -> Result<()> {
+        let length = self.length();
+        self.paint_bg(0, length)?;
+
+        self.redraw_left(true, 0)?;
+        if !self.right_items.is_empty() {
+            let last = self.right_items.len() - 1;
+            self.redraw_right(true, last)?;
+        }
+        self.redraw_center()
+    }
+
+    /// Maps or unmaps the panel's window.
+    pub fn set_mapped(&self, mapped: bool) {
+        if mapped {
+            xcb::map_window(&self.conn, self.window);
+        } else {
+            xcb::unmap_window(&self.conn, self.window);
+        }
+        self.conn.flush();
+    }
+
+    /// Whether the pointer is currently on the panel's window.
+    pub fn has_pointer(&self) -> bool {
+        let reply = match xcb::query_pointer(&self.conn, self.window).get_reply() {
+            Ok(reply) => reply,
+            Err(_) => return false,
+        };
+        let (x, y) = (reply.win_x(), reply.win_y());
+        reply.same_screen() && x >= 0 && y >= 0 && (x as u16) < self.geometry.width() &&
+            (y as u16) < self.geometry.height()
+    }
+
+    /// Moves and resizes the panel's window without changing the panel's geometry.
+    pub fn set_window_area(&self, area: &Rectangle) {
+        configure_window(&self.conn, self.window, area);
+    }
+
+    /// Returns every item of the panel, from left to right or from the top down.
+    pub fn items(&self) -> Vec<&ItemState> {
+        self.left_items
+            .iter()
+            .chain(self.center_items.iter())
+            .chain(self.right_items.iter())
+            .collect()
+    }
+
+    /// Marks an area of the panel as damaged.
+    /// Damaged areas are repainted by `repaint_damage`.
+    pub fn add_damage(&mut self, area: Rectangle) {
+        self.damage.push(area);
+    }
+
+    /// Marks the whole panel as damaged.
+    pub fn damage_all(&mut self) {
+        let area = Rectangle::new(0, 0, self.geometry.width(), self.geometry.height());
+        self.damage.push(area);
+    }
+
+    /// Repaints the damaged areas of the panel.
+    /// The background is repainted and every item overlapping
+    /// the damage is copied back to the window.
+    pub fn repaint_damage(&mut self) -> Result<()> {
+        let damage = ::std::mem::replace(&mut self.damage, vec![]);
+        if damage.is_empty() {
+            return Ok(());
+        }
+
+        try_xcb!(
+            xcb::poly_fill_rectangle,
+            "failed to draw background",
+            &self.conn,
+            self.window,
+            self.foreground,
+            &damage
+        );
+
+        let items = self.left_items
+            .iter()
+            .chain(self.center_items.iter())
+            .chain(self.right_items.iter());
+
+        let vertical = self.is_vertical();
+        for item in items {
+            let (start, width) = self.item_positions[item.get_id()];
+            let end = start as i32 + width as i32;
+            let damaged = damage.iter().any(|rect| {
+                let (rect_start, rect_length) = if vertical {
+                    (rect.y() as i32, rect.height() as i32)
+                } else {
+                    (rect.x() as i32, rect.width() as i32)
+                };
+                rect_start < end && rect_start + rect_length > start as i32
+            });
+
+            if damaged {
+                self.draw_item(item, start)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Redraws components in the center slot.
+    /// Unforunately centering means that all components
+    /// must be redrawn if even one of them changes size.
+    fn redraw_center(&mut self) -> Result<()> {
+        // Get future width of all center components
+        let width_all: u16 = self.center_items
+            .iter()
+            .map(|item| item.get_content_width())
+            .sum();
+
+        // Draw blank background to prevent leftovers after shrinkage
+        // Only does this when component width has shrunk
+        let old_width_all: u16 = self.center_items
+            .iter()
+            .map(|item| self.item_positions[item.get_id()].1)
+            .sum();
+        if width_all < old_width_all {
+            if let Some(first) = self.center_items.first() {
+                let old_start = self.item_positions[first.get_id()].0;
+                self.paint_bg(old_start, old_start + old_width_all)?;
+            }
+        }
+
+        let mut pos = self.length() / 2 - width_all / 2;
+
+        for item in &self.center_items {
+            self.item_positions[item.get_id()].0 = pos;
+            self.draw_item(item, pos)?;
+            self.item_positions[item.get_id()].1 = item.get_content_width();
+            pos += item.get_content_width();
+        }
+
+        Ok(())
+    }
+
+    /// Pretty much the same as `self.redraw_left` but with `left` replaced with `right`.
+    /// The order in which the items are gone through is reversed.
+    fn redraw_right(&mut self, size_changed: bool, index: usize) -> Result<()> {
+        let mut pos = self.length() - self.inner_padding;
+
+        for n in 0..self.right_items.len() {
+            let item = &self.right_items[self.right_items.len() - n - 1];
+
+            pos -= item.get_content_width();
+
+            if n < self.right_items.len() - index - 1 {
+                continue;
+            }
+
+            if size_changed {
+                let mut bg_start = pos;
+                let bg_end = pos + item.get_content_width();
+
+                if n == self.right_items.len() - 1 {
+                    let old_start = self.item_positions[item.get_id()].0 as u16;
+                    if old_start < bg_start && old_start > 0 {
+                        bg_start = old_start;
+                    }
+                }
+
+                self.paint_bg(bg_start, bg_end)?;
+            }
+
+            self.item_positions[item.get_id()].0 = pos;
+            self.item_positions[item.get_id()].1 = item.get_content_width();
+            self.draw_item(item, pos)?;
+
+            if !size_changed {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Redraw only needed items in the right slot.
+    /// Symmetric to `self.redraw_left`.
+    ///
+    /// If the component hasn't changed it's size, it doesn't affect
+    /// any other components and we can get away with just painting
+    /// the one component.
+    ///
+    /// However if the component has changed it's size, we must also
+    /// redraw every component on the right of it. If the item has shrunk
+    /// we must also repaint the exposed background.
+    fn redraw_left(&mut self, size_changed: bool, index: usize) -> Result<()> {
+        let mut pos = self.inner_padding;
+
+        for n in 0..self.left_items.len() {
+            let item = &self.left_items[n];
+
+            if n < index {
+                pos += item.get_content_width();
+                continue;
+            }
+
+            if size_changed {
+                let bg_start = pos;
+                let mut bg_end = pos + item.get_content_width();
+
+                if n == self.left_items.len() - 1 {
+                    let old_end =
+                        self.item_positions[item.get_id()].0 + self.item_positions[item.get_id()].1;
+                    if bg_end < old_end {
+                        bg_end = old_end;
+                    }
+                }
+
+                self.paint_bg(bg_start, bg_end)?;
+            }
+
+            self.item_positions[item.get_id()].0 = pos;
+            self.item_positions[item.get_id()].1 = item.get_content_width();
+            self.draw_item(item, pos)?;
+
+            if !size_changed {
+                break;
+            }
+
+            pos += item.get_content_width();
+        }
+
+        Ok(())
+    }
+
+    /// Copies the item's pixmap to the window.
+    fn draw_item(&self, item: &ItemState, pos: u16) -> Result<()> {
+        if !item.is_ready() {
+            return Ok(());
+        }
+
+        let area = self.span(pos, item.get_content_width());
+        try_xcb!(
+            xcb::copy_area_checked,
+            "failed to copy pixmap",
+            &self.conn,
+            item.get_pixmap(),
+            self.window,
+            self.foreground,
+            0,
+            0,
+            area.x(),
+            area.y(),
+            area.width(),
+            area.height()
+        );
+
+        Ok(())
+    }
+
+    /// Draws the background starting at point a along the panel until point b.
+    fn paint_bg(&self, a: u16, b: u16) -> Result<()> {
+        try_xcb!(
+            xcb::poly_fill_rectangle,
+            "failed to draw background",
+            &self.conn,
+            self.window,
+            self.foreground,
+            &[self.span(a, b - a)]
+        );
+
+        Ok(())
+    }
+}
+
+impl Drop for Panel {
+    fn drop(&mut self) {
+        xcb::destroy_window(&self.conn, self.window);
+        self.conn.flush();
+    }
+}
+
+fn configure_window(conn: &Connection, window: Window, area: &Rectangle) {
+    xcb::configure_window(
+        conn,
+        window,
+        &[
+            (xcb::CONFIG_WINDOW_X as u16, area.x() as u32),
+            (xcb::CONFIG_WINDOW_Y as u16, area.y() as u32),
+            (xcb::CONFIG_WINDOW_WIDTH as u16, area.width() as u32),
+            (xcb::CONFIG_WINDOW_HEIGHT as u16, area.height() as u32),
+        ],
+    );
+    conn.flush();
+}
+
+/// Time between the frames of a sliding panel.
+const FRAME_DURATION: u64 = 16;
+
+/// How many pixels of a collapsed panel are left on it's output for the pointer to enter.
+const TRIGGER_SIZE: u16 = 2;
+
+/// Slides the window of a panel from one area to another over `duration`.
+///
+/// The window is unmapped once it arrives if `unmap` is set. Starting a slide
+/// stops the slide of the panel that hasn't finished yet.
+pub fn slide(
+    panel: &Panel,
+    timer: &Timer,
+    from: Rectangle,
+    to: Rectangle,
+    duration: Duration,
+    unmap: bool,
+) -> Box<Future<Item = (), Error = ()>> {
+    let animation = panel.animation.clone();
+    let generation = animation.get() + 1;
+    animation.set(generation);
+
+    let millis = duration.as_secs() * 1000 + duration.subsec_nanos() as u64 / 1_000_000;
+    let frames = (millis / FRAME_DURATION).max(1);
+
+    let conn = panel.conn.clone();
+    let window = panel.window;
+
+    let future = timer
+        .interval(Duration::from_millis(FRAME_DURATION))
+        .take(frames)
+        .map_err(|_| ())
+        .fold(1, move |frame, ()| {
+            if animation.get() != generation {
+                return Err(());
+            }
+
+            let progress = frame as f64 / frames as f64;
+            let step = |a: i32, b: i32| a + ((b - a) as f64 * progress).round() as i32;
+            let area = Rectangle::new(
+                step(from.x() as i32, to.x() as i32) as i16,
+                step(from.y() as i32, to.y() as i32) as i16,
+                step(from.width() as i32, to.width() as i32) as u16,
+                step(from.height() as i32, to.height() as i32) as u16,
+            );
+            configure_window(&conn, window, &area);
+
+            if frame == frames && unmap {
+                xcb::unmap_window(&conn, window);
+                conn.flush();
+            }
+            Ok(frame + 1)
+        })
+        .map(|_| ());
+
+    Box::new(future)
+}
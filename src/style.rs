@@ -0,0 +1,57 @@
+use color::Color;
+
+/// Styling of a single component's item.
+///
+/// Anything left unset falls back to the bar-wide settings.
+/// The item is laid out as margin, border, padding and the content itself.
+#[derive(Clone, Default)]
+pub struct Style {
+    /// The text color of the item.
+    pub foreground: Option<Color>,
+    /// The background color of the item's box.
+    pub background: Option<Color>,
+    /// The font of the item, as a Pango font description.
+    pub font: Option<String>,
+    /// Space between the left border and the content in pixels.
+    pub padding_left: u16,
+    /// Space between the content and the right border in pixels.
+    pub padding_right: u16,
+    /// Space outside the item's box on the left in pixels.
+    pub margin_left: u16,
+    /// Space outside the item's box on the right in pixels.
+    pub margin_right: u16,
+    pub border: Option<Border>,
+}
+
+/// A border drawn around an item's box.
+#[derive(Clone)]
+pub struct Border {
+    /// Width of the border in pixels.
+    pub width: u16,
+    pub color: Color,
+}
+
+impl Style {
+    /// Create an empty `Style`, which uses the bar-wide settings.
+    pub fn new() -> Style {
+        Style::default()
+    }
+
+    /// Width of the border, or zero if there is none.
+    #[inline]
+    pub fn border_width(&self) -> u16 {
+        self.border.as_ref().map_or(0, |border| border.width)
+    }
+
+    /// Space on the left side of the content in pixels.
+    #[inline]
+    pub fn inset_left(&self) -> u16 {
+        self.margin_left + self.border_width() + self.padding_left
+    }
+
+    /// Space on the right side of the content in pixels.
+    #[inline]
+    pub fn inset_right(&self) -> u16 {
+        self.padding_right + self.border_width() + self.margin_right
+    }
+}
@@ -2,9 +2,10 @@
 macro_rules! composite {
     ( $( $arg:expr ),+ ) => {
         {
-            use $crate::component::{Component, SubComponent};
+            use $crate::component::{Component, SubComponent, ItemContent, RefreshStream};
             use $crate::Error;
-            use ::futures::{Poll, Async};
+            use ::futures::{Async, Future, Poll};
+            use ::futures::unsync::mpsc;
 
             struct CompositeComponent {
                 components: Vec<SubComponent>,
@@ -21,31 +22,82 @@ macro_rules! composite {
                     Ok(())
                 }
 
+                fn refreshable(&self) -> bool {
+                    self.components.iter().any(|component| component.0.refreshable())
+                }
+
                 fn stream(self, handle: Handle) -> Self::Stream {
+                    let refreshes = self.components.iter().map(|_| None).collect();
+                    CompositeComponentStream::new(self.components, refreshes, handle)
+                }
+
+                // Refreshing the composite refreshes every part of it that can be
+                fn refreshed_stream(self, handle: Handle, refreshes: RefreshStream) -> Self::Stream {
+                    let mut senders = vec![];
+                    let parts = self.components
+                        .iter()
+                        .map(|component| {
+                            if !component.0.refreshable() {
+                                return None;
+                            }
+                            let (tx, rx) = mpsc::unbounded();
+                            senders.push(tx);
+                            Some(Box::new(rx.map_err(|()| Error::from("channel error"))) as
+                                     RefreshStream)
+                        })
+                        .collect();
+
+                    handle.spawn(
+                        refreshes
+                            .for_each(move |()| {
+                                for sender in &senders {
+                                    let _ = sender.unbounded_send(());
+                                }
+                                Ok(())
+                            })
+                            .map_err(|_| ()),
+                    );
+                    CompositeComponentStream::new(self.components, parts, handle)
+                }
+            }
+
+            struct CompositeComponentStream {
+                streams: Vec<Box<Stream<Item=(usize, ItemContent), Error=Error>>>,
+                states: Vec<ItemContent>,
+            };
+
+            impl CompositeComponentStream {
+                fn new(
+                    components: Vec<SubComponent>,
+                    refreshes: Vec<Option<RefreshStream>>,
+                    handle: Handle,
+                ) -> CompositeComponentStream {
                     CompositeComponentStream {
-                        states: vec![String::new(); self.components.len()],
-                        streams: self.components
+                        states: vec![ItemContent::Markup(String::new()); components.len()],
+                        streams: components
                             .into_iter()
+                            .zip(refreshes)
                             .enumerate()
-                            .map(|(id, c)| {
-                                Box::new(c.0.into_stream(handle.clone()).map(move |s| (id, s))) as
-                                    Box<Stream<Item = (usize, String), Error = Error>>
+                            .map(|(id, (c, refreshes))| {
+                                let stream = match refreshes {
+                                    Some(refreshes) => {
+                                        c.0.into_refreshed_stream(handle.clone(), refreshes)
+                                    }
+                                    None => c.0.into_stream(handle.clone()),
+                                };
+                                Box::new(stream.map(move |s| (id, s))) as
+                                    Box<Stream<Item = (usize, ItemContent), Error = Error>>
                             })
                             .collect(),
                     }
                 }
             }
 
-            struct CompositeComponentStream {
-                streams: Vec<Box<Stream<Item=(usize, String), Error=Error>>>,
-                states: Vec<String>,
-            };
-
             impl Stream for CompositeComponentStream {
-                type Item = String;
+                type Item = ItemContent;
                 type Error = Error;
 
-                fn poll(&mut self) -> Poll<Option<String>, Error> {
+                fn poll(&mut self) -> Poll<Option<ItemContent>, Error> {
                     let mut do_update = false;
                     for stream in &mut self.streams {
                         match stream.poll() {
@@ -58,9 +110,22 @@ macro_rules! composite {
                         }
                     }
 
-                    match do_update {
-                        true => Ok(Async::Ready(Some(self.states.concat()))),
-                        false => Ok(Async::NotReady),
+                    if !do_update {
+                        return Ok(Async::NotReady);
+                    }
+
+                    // The whole composite is urgent if any of it's parts is
+                    let urgent = self.states.iter().any(|state| state.is_urgent());
+                    let markup = self.states
+                        .iter()
+                        .cloned()
+                        .map(|state| state.into_markup())
+                        .collect::<Vec<_>>()
+                        .concat();
+
+                    match urgent {
+                        true => Ok(Async::Ready(Some(ItemContent::Urgent(markup)))),
+                        false => Ok(Async::Ready(Some(ItemContent::Markup(markup)))),
                     }
                 }
             }
@@ -1,8 +1,10 @@
 mod stream_loop_fn;
+mod x11;
 #[macro_use]
 mod composite;
 
 pub use self::stream_loop_fn::LoopFn;
+pub(crate) use self::x11::{connect_xkb, intern_atom, CORE_KEYBOARD};
 
 macro_rules! try_xcb {
     ($func:expr, $error:expr, $($args:expr),*) => {
@@ -0,0 +1,22 @@
+use error::{ErrorKind, Result};
+use xcb::{self, Connection, xkb};
+
+/// The keyboard XKB requests are made for.
+pub(crate) const CORE_KEYBOARD: xkb::DeviceSpec = xkb::ID_USE_CORE_KBD as xkb::DeviceSpec;
+
+/// Connects to X and makes sure the XKB extension can be used.
+pub(crate) fn connect_xkb() -> Result<(Connection, i32)> {
+    let (conn, screen_num) = Connection::connect(None).map_err(ErrorKind::XcbConnection)?;
+
+    let reply = xkb::use_extension(&conn, 1, 0).get_reply()?;
+    if !reply.supported() {
+        return Err("The XKB extension is not supported.".into());
+    }
+
+    Ok((conn, screen_num))
+}
+
+/// Interns an atom, failing if the reply can't be received.
+pub(crate) fn intern_atom(conn: &Connection, name: &str) -> Result<xcb::Atom> {
+    Ok(xcb::intern_atom(conn, false, name).get_reply()?.atom())
+}
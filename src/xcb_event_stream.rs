@@ -5,8 +5,9 @@ use futures::{Async, Poll, Stream};
 use error::{Result, Error};
 use mio;
 use std::io;
+use std::rc::Rc;
 
-struct InnerStream(Connection);
+struct InnerStream(Rc<Connection>);
 
 impl AsRawFd for InnerStream {
     fn as_raw_fd(&self) -> RawFd {
@@ -45,7 +46,7 @@ pub struct XcbEventStream {
 }
 
 impl XcbEventStream {
-    pub fn new(conn: Connection, handle: &Handle) -> Result<XcbEventStream> {
+    pub fn new(conn: Rc<Connection>, handle: &Handle) -> Result<XcbEventStream> {
         Ok(XcbEventStream {
             io: PollEvented::new(InnerStream(conn), handle)?,
         })